@@ -0,0 +1,228 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::ast_types::Number;
+
+// Associativity is checked on every triple of elements
+pub const ORDER_LIMIT: usize = 256;
+
+// A finite set with its operations worked out as Cayley tables of indices into the elements. The
+// first operation is the group operation, or the addition of a ring
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Structure {
+    pub elements: Vec<Number>,
+    pub tables:   Vec<Vec<Vec<usize>>>,
+    pub identity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Axiom {
+    Closure,
+    Associativity,
+    Identity,
+    Inverses,
+    Commutativity,
+    Distributivity,
+}
+
+// The axiom that doesn't hold and the elements it fails for
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxiomFailure {
+    pub axiom:    Axiom,
+    pub elements: Vec<Number>,
+}
+
+impl Structure {
+    // products[k][a][b] is a * b under the k-th operation. The first has to make a group, a second
+    // makes a ring, so then the first is commutative and the second associative and distributive
+    pub fn new(elements: Vec<Number>, products: Vec<Vec<Vec<Number>>>) -> Result<Self, AxiomFailure> {
+        let failure = |axiom, indices: &[usize]| AxiomFailure { axiom, elements: indices.iter().map(|i| elements[*i].clone()).collect() };
+        let mut tables = vec![];
+        for products in &products {
+            let mut table = vec![];
+            for (a, row) in products.iter().enumerate() {
+                let mut indices = vec![];
+                for (b, product) in row.iter().enumerate() {
+                    indices.push(elements.iter().position(|element| element == product).ok_or_else(|| failure(Axiom::Closure, &[a, b]))?);
+                }
+                table.push(indices);
+            }
+            tables.push(table);
+        }
+
+        let Some(table) = tables.first() else {
+            return Err(failure(Axiom::Identity, &[]));
+        };
+        associative(table).map_err(|triple| failure(Axiom::Associativity, &triple))?;
+        let identity = (0..elements.len()).find(|e| (0..elements.len()).all(|a| table[*e][a] == a && table[a][*e] == a))
+            .ok_or_else(|| failure(Axiom::Identity, &[]))?;
+        if let Some(a) = (0..elements.len()).find(|a| !(0..elements.len()).any(|b| table[*a][b] == identity && table[b][*a] == identity)) {
+            return Err(failure(Axiom::Inverses, &[a]));
+        }
+        if let Some(multiplication) = tables.get(1) {
+            let pairs = (0..elements.len()).flat_map(|a| (0..elements.len()).map(move |b| (a, b)));
+            if let Some((a, b)) = pairs.clone().find(|(a, b)| table[*a][*b] != table[*b][*a]) {
+                return Err(failure(Axiom::Commutativity, &[a, b]));
+            }
+            associative(multiplication).map_err(|triple| failure(Axiom::Associativity, &triple))?;
+            // a(b + c) = ab + ac and (a + b)c = ac + bc
+            for (a, b) in pairs {
+                for c in 0..elements.len() {
+                    if multiplication[a][table[b][c]] != table[multiplication[a][b]][multiplication[a][c]]
+                        || multiplication[table[a][b]][c] != table[multiplication[a][c]][multiplication[b][c]] {
+                        return Err(failure(Axiom::Distributivity, &[a, b, c]));
+                    }
+                }
+            }
+        }
+        Ok(Self { elements, tables, identity })
+    }
+
+    pub fn index(&self, element: &Number) -> Option<usize> {
+        self.elements.iter().position(|other| other == element)
+    }
+
+    pub fn operate(&self, a: usize, b: usize) -> usize {
+        self.tables[0][a][b]
+    }
+
+    // The smallest n > 0 with a^n = e
+    pub fn order(&self, a: usize) -> usize {
+        let mut power = a;
+        let mut order = 1;
+        while power != self.identity {
+            power = self.operate(power, a);
+            order += 1;
+        }
+        order
+    }
+
+    // Pairs (a, b) with both operations worked out a component at a time, a ring when both are
+    pub fn product(&self, other: &Structure) -> Structure {
+        let size = other.elements.len();
+        let elements = self.elements.iter()
+            .flat_map(|a| other.elements.iter().map(move |b| Number::Tuple(vec![a.clone(), b.clone()])))
+            .collect();
+        let tables = self.tables.iter().zip(&other.tables).map(|(first, second)| {
+            (0..self.elements.len() * size).map(|x| (0..self.elements.len() * size).map(|y| {
+                first[x / size][y / size] * size + second[x % size][y % size]
+            }).collect()).collect()
+        }).collect();
+        Structure { elements, tables, identity: self.identity * size + other.identity }
+    }
+}
+
+// Elements of a structure, all of them, a subgroup or a coset
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Subset {
+    pub structure: Rc<Structure>,
+    // Sorted indices into the elements
+    pub members: Vec<usize>,
+}
+
+impl Subset {
+    pub fn whole(structure: Structure) -> Self {
+        let members = (0..structure.elements.len()).collect();
+        Self { structure: Rc::new(structure), members }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Number> {
+        self.members.iter().map(|i| &self.structure.elements[*i])
+    }
+
+    // Closure is enough when there are finitely many elements
+    pub fn is_subgroup(&self) -> bool {
+        !self.members.is_empty() && self.members.iter()
+            .all(|a| self.members.iter().all(|b| self.members.binary_search(&self.structure.operate(*a, *b)).is_ok()))
+    }
+
+    // The smallest subgroup containing the generators
+    pub fn generated(structure: Rc<Structure>, generators: &[usize]) -> Self {
+        let mut members = vec![structure.identity];
+        let mut new = generators.to_vec();
+        while let Some(a) = new.pop() {
+            if members.contains(&a) {
+                continue;
+            }
+            members.push(a);
+            new.extend(members.iter().flat_map(|b| [structure.operate(a, *b), structure.operate(*b, a)]));
+        }
+        members.sort();
+        Self { structure, members }
+    }
+
+    // aH, or Ha on the right
+    pub fn coset(&self, a: usize, right: bool) -> Self {
+        let mut members = self.members.iter()
+            .map(|h| if right { self.structure.operate(*h, a) } else { self.structure.operate(a, *h) })
+            .collect::<Vec<_>>();
+        members.sort();
+        members.dedup();
+        Self { structure: Rc::clone(&self.structure), members }
+    }
+
+    // HK, when H and K are subgroups that only share the identity and commute with each other it
+    // is isomorphic to H \times K
+    pub fn internal_product(&self, other: &Subset) -> Option<Self> {
+        if self.structure != other.structure || !self.is_subgroup() || !other.is_subgroup() {
+            return None;
+        }
+        if self.members.iter().any(|h| *h != self.structure.identity && other.members.contains(h)) {
+            return None;
+        }
+        let mut members = vec![];
+        for h in &self.members {
+            for k in &other.members {
+                if self.structure.operate(*h, *k) != self.structure.operate(*k, *h) {
+                    return None;
+                }
+                members.push(self.structure.operate(*h, *k));
+            }
+        }
+        members.sort();
+        members.dedup();
+        Some(Self { structure: Rc::clone(&self.structure), members })
+    }
+
+    // A subgroup as a group of its own, it stays a ring when it's closed under multiplication
+    pub fn to_structure(&self) -> Structure {
+        let position = |a: usize| self.members.binary_search(&a).ok();
+        let tables = self.structure.tables.iter()
+            .map(|table| self.members.iter().map(|a| self.members.iter().map(|b| position(table[*a][*b])).collect::<Option<Vec<_>>>()).collect::<Option<Vec<_>>>())
+            .map_while(|table| table)
+            .collect();
+        Structure {
+            elements: self.elements().cloned().collect(),
+            tables,
+            identity: position(self.structure.identity).unwrap_or_default(),
+        }
+    }
+}
+
+impl Display for AxiomFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.axiom, &self.elements[..]) {
+            (Axiom::Closure, [a, b])           => write!(f, "closure fails, {a} and {b} give something outside the set"),
+            (Axiom::Associativity, [a, b, c])  => write!(f, "associativity fails for {a}, {b} and {c}"),
+            (Axiom::Identity, _)               => write!(f, "there is no identity"),
+            (Axiom::Inverses, [a])             => write!(f, "{a} has no inverse"),
+            (Axiom::Commutativity, [a, b])     => write!(f, "addition isn't commutative for {a} and {b}"),
+            (Axiom::Distributivity, [a, b, c]) => write!(f, "distributivity fails for {a}, {b} and {c}"),
+            (axiom, _) => write!(f, "{axiom:?} fails"),
+        }
+    }
+}
+
+// Fails with the first triple that isn't
+fn associative(table: &[Vec<usize>]) -> Result<(), [usize; 3]> {
+    for a in 0..table.len() {
+        for b in 0..table.len() {
+            for c in 0..table.len() {
+                if table[table[a][b]][c] != table[a][table[b][c]] {
+                    return Err([a, b, c]);
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -107,16 +107,127 @@ pub enum BinOps {
     InternalDirectProduct,
     Union,
     Intersection,
+    // \binom{n}{k}
+    Binomial,
+    // a \bmod b, always between 0 and b
+    Modulo,
+    // x^2, right associative
+    Power,
     // TODO Add more
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnOps {
     Negation,
-    BoolNot
+    BoolNot,
+    // n!, written after its operand
+    Factorial,
+    AbsoluteValue,
+    Floor,
+    Ceiling,
     // TODO Add more
 }
 
+// Operator names with a meaning built in, \sin x, \gcd(a, b), \operatorname{lcm}(a, b)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Builtin {
+    Sin,
+    Cos,
+    Tan,
+    Sec,
+    Csc,
+    Cot,
+    Arcsin,
+    Arccos,
+    Arctan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Coth,
+    Ln,
+    // \log_b x, base 10 without a subscript
+    Log,
+    Exp,
+    Gcd,
+    Lcm,
+    Min,
+    Max,
+    // Of its arguments as a square matrix, row by row
+    Det,
+    // \operatorname{rank} A, \operatorname{rref} A, of a matrix
+    Rank,
+    Rref,
+    // \operatorname{roots}(p) of a polynomial
+    Roots,
+    // \operatorname{ord}_G(a), and \langle a, b \rangle_G, the group comes last like the base of a logarithm
+    Order,
+    Generated,
+    // \operatorname{expand}(x), \operatorname{factor}(x), for symbolic expressions
+    Expand,
+    Factor,
+    // \frac{d}{dx} f, the variable comes after the expression like the base of a logarithm
+    Derivative,
+    // \int f \, dx and \int_a^b f \, dx, the variable then the bounds after the expression
+    Integral,
+    // a \equiv b \pmod{n}, the modulus is optional
+    Congruence,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin"    => Builtin::Sin,
+            "cos"    => Builtin::Cos,
+            "tan"    => Builtin::Tan,
+            "sec"    => Builtin::Sec,
+            "csc"    => Builtin::Csc,
+            "cot"    => Builtin::Cot,
+            "arcsin" => Builtin::Arcsin,
+            "arccos" => Builtin::Arccos,
+            "arctan" => Builtin::Arctan,
+            "sinh"   => Builtin::Sinh,
+            "cosh"   => Builtin::Cosh,
+            "tanh"   => Builtin::Tanh,
+            "coth"   => Builtin::Coth,
+            "ln"     => Builtin::Ln,
+            "log"    => Builtin::Log,
+            "exp"    => Builtin::Exp,
+            "gcd"    => Builtin::Gcd,
+            "lcm"    => Builtin::Lcm,
+            "min"    => Builtin::Min,
+            "max"    => Builtin::Max,
+            "det"    => Builtin::Det,
+            "rank"   => Builtin::Rank,
+            "rref"   => Builtin::Rref,
+            "roots"  => Builtin::Roots,
+            "ord"    => Builtin::Order,
+            "expand" => Builtin::Expand,
+            "factor" => Builtin::Factor,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // Not LaTeX commands
+            Builtin::Lcm    => write!(f, "\\operatorname{{lcm}}"),
+            Builtin::Rank   => write!(f, "\\operatorname{{rank}}"),
+            Builtin::Rref   => write!(f, "\\operatorname{{rref}}"),
+            Builtin::Roots  => write!(f, "\\operatorname{{roots}}"),
+            Builtin::Order  => write!(f, "\\operatorname{{ord}}"),
+            Builtin::Generated => write!(f, "\\langle\\rangle"),
+            Builtin::Expand => write!(f, "\\operatorname{{expand}}"),
+            Builtin::Factor => write!(f, "\\operatorname{{factor}}"),
+            Builtin::Derivative => write!(f, "\\frac{{d}}{{dx}}"),
+            Builtin::Integral   => write!(f, "\\int"),
+            Builtin::Congruence => write!(f, "\\equiv"),
+            builtin => write!(f, "\\{}", format!("{builtin:?}").to_lowercase()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Conditionals {
     Equals,
@@ -132,6 +243,22 @@ pub enum Conditionals {
     // TODO Add more
 }
 
+impl Display for Conditionals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conditionals::Equals    => write!(f, "="),
+            Conditionals::Approx    => write!(f, "\\approx"),
+            Conditionals::LessEq    => write!(f, "\\leq"),
+            Conditionals::GreaterEq => write!(f, "\\geq"),
+            Conditionals::Less      => write!(f, "<"),
+            Conditionals::Greater   => write!(f, ">"),
+            Conditionals::Congruent => write!(f, "\\cong"),
+            Conditionals::In        => write!(f, "\\in"),
+            Conditionals::NotIn     => write!(f, "\\notin"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum SetOps {
     Union,
@@ -168,5 +295,3 @@ pub enum Loops {
     And,
     Or,
 }
-
-// Where put Integrals and Derivative?
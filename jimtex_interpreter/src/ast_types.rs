@@ -1,9 +1,18 @@
 use std::collections::VecDeque;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 
+use num::bigint::Sign;
 use num::{BigInt, BigRational};
 use crate::lexer::Token;
-use crate::ast::{BinOps, Conditionals, GreekLetters, SetOps, UnOps};
+use crate::format::{FormatOption, NumberFormat, Renderer};
+use crate::real::{Real, Rounding};
+use crate::symbolic::Symbolic;
+use crate::errors::ParseError;
+use crate::matrix::Matrix;
+use crate::polynomial::Polynomial;
+use crate::algebra::Subset;
+use crate::ast::{BinOps, Builtin, Conditionals, GreekLetters, SetOps, UnOps};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
@@ -33,6 +42,42 @@ pub enum Statement {
     Expression(Expression),
     Declaration(Declaration),
     FunctionDefinition(FunctionDefinition),
+    SequenceDefinition(SequenceDefinition),
+    Pragma(Pragma),
+    // \jimtexformat{fixed 2}{x}
+    FormattedExpression(Expression, Vec<FormatOption>),
+    // Couldn't be parsed, an error once it's reached
+    Invalid(ParseError),
+}
+
+// %jimtex: directives, they apply to everything after them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pragma {
+    // %jimtex: precision 10, digits after the decimal point when printing reals
+    Precision(usize),
+    // %jimtex: strict, every function needs a declaration before its definition
+    Strict(bool),
+    // %jimtex: exact, \pi stays symbolic instead of becoming a real
+    Exact(bool),
+    // %jimtex: symbolic, identifiers without a value stay as variables
+    Symbolic(bool),
+    // %jimtex: format scientific 3, %jimtex: fractions mixed, %jimtex: thousands comma
+    Format(FormatOption),
+    // %jimtex: digits 50, significant digits kept when a real can't be exact
+    Digits(usize),
+    // %jimtex: rounding half-up
+    Rounding(Rounding),
+    // %jimtex: multiletter product, only read by the parser
+    MultiLetter(MultiLetter),
+    // Misspelled or with an invalid value, ignored with a warning
+    Unknown(String),
+}
+
+// Whether ab is one identifier or a times b
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultiLetter {
+    Identifier,
+    Product,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -47,8 +92,39 @@ pub enum Expression {
     Value(Box<Value>),
     Conditional(Conditional),
     FunctionCall(FunctionCall),
+    BuiltinCall(BuiltinCall),
+    SequenceTerm(SequenceTerm),
     UnaryOperation(UnaryOperation),
     BinaryOperation(BinaryOperation),
+    Limit(Limit),
+    // The rows of a pmatrix, evaluated into a Number::Matrix
+    Matrix(Vec<Vec<Value>>),
+    Tuple(Vec<Value>),
+    Structure(StructureLiteral),
+    // A part that couldn't be parsed
+    Invalid(ParseError),
+}
+
+// (\Z/6\Z, +), (\{1, 2, 3, 4\}, m), (\{0, 1\}, \begin{pmatrix} 0 & 1 \\ 1 & 0 \end{pmatrix}), a finite
+// group from a set and an operation, or a ring with a second one
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureLiteral {
+    pub carrier:    Carrier,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Carrier {
+    Elements(Vec<Value>),
+    // \Z/n\Z
+    Set(Token),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    BinOp(BinOps),
+    // A function of two arguments or a Cayley table, a row for each element in order
+    Value(Value),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -77,10 +153,44 @@ pub struct FunctionCall {
     pub args: Vec<Value>,
 }
 
+// \sin x, \log_2 8, the base of a logarithm comes after its argument
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltinCall {
+    pub function: Builtin,
+    pub args: Vec<Value>,
+}
+
+// \lim_{x \to a} f, \lim_{x \to a^+} f from above
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limit {
+    pub variable:   Identifier,
+    pub approaches: Value,
+    pub side:       Side,
+    pub value:      Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Both,
+    // a^-
+    Left,
+    // a^+
+    Right,
+}
+
+// a_{n-1}, any subscript that isn't a plain identifier or number
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceTerm {
+    pub sequence: Identifier,
+    pub index: Value,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Identifier {
     GreekLetter(GreekLetters),
     TextIdent(String),
+    // Only valid as a subscript, a_0
+    NumberIdent(BigInt),
     SubScriptIdent(Box<SubScriptIdent>),
 }
 
@@ -93,15 +203,22 @@ pub struct SubScriptIdent {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Number {
     Integer(BigInt),
-    Real(f64),
+    Real(Real),
     Complex(Complex),
     Rational(BigRational),
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Real {
-    pub int_part: BigInt,
-    pub decimal_part: BigInt,
+    // A rational multiple of \pi, only in exact mode
+    PiMultiple(BigRational),
+    // \infty and -\infty
+    Infinity(Sign),
+    // An expression with free variables, only in symbolic mode
+    Symbolic(Box<Symbolic>),
+    Matrix(Box<Matrix>),
+    // A function that is a polynomial in its argument, as a value
+    Polynomial(Box<Polynomial>),
+    // (a, b), an element of a direct product
+    Tuple(Vec<Number>),
+    // A finite group or ring, or a subgroup or coset of one
+    Set(Box<Subset>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -119,7 +236,8 @@ pub struct Condition {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Declaration {
-    FunctionDeclaration(FunctionDeclaration),
+    // Domains are tokens, which are large
+    FunctionDeclaration(Box<FunctionDeclaration>),
     ValueDeclaration(ValueDeclaration),
     SetDeclaration(SetDeclaration),
 }
@@ -129,6 +247,18 @@ pub struct FunctionDeclaration {
     pub identifier: Identifier,
     pub domain: Token,
     pub codomain: Token,
+    // Declared with \memo, results are cached per argument tuple
+    pub memoized: bool,
+}
+
+// \R^n, \Z_{\geq 0}, a plain \R stays a token
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberSet {
+    pub set: Box<Token>,
+    pub restriction: Option<(Conditionals, Number)>,
+    pub power: Option<Value>,
+    // \Z/n\Z
+    pub modulus: Option<Value>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -164,13 +294,68 @@ pub struct FunctionDefinition {
     pub expression: Expression
 }
 
+// a_n = a_{n-1} + a_{n-2}, base cases are value declarations of a_0, a_1, ...
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceDefinition {
+    pub identifier: Identifier,
+    pub index:      Identifier,
+    pub expression: Expression
+}
+
 impl Display for Number {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", NumberFormat::default().render(self, Renderer::Plain))
+    }
+}
+
+// Numbers are used as keys for memoized function calls. The parts of a complex number are hashed
+// as exact decimals, so 0.0 and -0.0 are the same key like they are equal
+impl Eq for Number {}
+
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
         match self {
-            Number::Real(real)    => write!(f, "{real:?}"),
-            Number::Integer(int)  => write!(f, "{int:?}"),
-            Number::Rational(rat) => write!(f, "{rat:?}"),
-            Number::Complex(cplx) => write!(f, "{cplx:?}"),
+            Number::Integer(int)  => int.hash(state),
+            Number::Real(real)    => real.hash(state),
+            Number::Rational(rat) => rat.hash(state),
+            Number::PiMultiple(coefficient) => coefficient.hash(state),
+            Number::Infinity(sign) => sign.hash(state),
+            Number::Symbolic(symbolic) => symbolic.to_string().hash(state),
+            Number::Matrix(matrix) => matrix.hash(state),
+            Number::Polynomial(polynomial) => polynomial.hash(state),
+            Number::Tuple(elements) => elements.hash(state),
+            Number::Set(set) => set.hash(state),
+            Number::Complex(cplx) => {
+                Real::from_f64(cplx.real).hash(state);
+                Real::from_f64(cplx.imag).hash(state);
+            },
+        }
+    }
+}
+
+impl Display for NumberSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self.set {
+            Token::Integers        => write!(f, "\\mathbb{{Z}}")?,
+            Token::RealNumbers     => write!(f, "\\mathbb{{R}}")?,
+            Token::RationalNumbers => write!(f, "\\mathbb{{Q}}")?,
+            Token::ComplexNumbers  => write!(f, "\\mathbb{{C}}")?,
+            Token::NatrualNumbers  => write!(f, "\\mathbb{{N}}")?,
+            _ => unreachable!(),
+        }
+        if let Some((condition, bound)) = &self.restriction {
+            write!(f, "_{{{condition} {bound}}}")?;
+        }
+        match &self.power {
+            Some(Value::Number(power))     => write!(f, "^{{{power}}}")?,
+            Some(Value::Identifier(power)) => write!(f, "^{{{power}}}")?,
+            _ => (),
+        }
+        match &self.modulus {
+            Some(Value::Number(modulus))     => write!(f, "/{modulus}\\mathbb{{Z}}"),
+            Some(Value::Identifier(modulus)) => write!(f, "/{modulus}\\mathbb{{Z}}"),
+            _ => Ok(()),
         }
     }
 }
@@ -180,6 +365,7 @@ impl Display for Identifier {
         match self {
             Identifier::TextIdent(tident) => write!(f, "{tident}"),
             Identifier::GreekLetter(lett) => write!(f, "{lett}"),
+            Identifier::NumberIdent(num)  => write!(f, "{num}"),
             Identifier::SubScriptIdent(s) => write!(f, "{s}")
 
         }
@@ -189,13 +375,19 @@ impl Display for Identifier {
 impl Display for SubScriptIdent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let SubScriptIdent { first_ident, secnd_ident } = self;
-        write!(f, "{first_ident}_{secnd_ident}")
+        // x_1 and \sigma_i, but x_{10} and x_{ij}
+        let subscript = secnd_ident.to_string();
+        if subscript.chars().count() == 1 {
+            write!(f, "{first_ident}_{subscript}")
+        } else {
+            write!(f, "{first_ident}_{{{subscript}}}")
+        }
     }
 }
 
 impl FunctionDeclaration {
     pub fn set_name(self, identifier: Identifier) -> Self {
-        Self { identifier, domain: self.domain, codomain: self.codomain }
+        Self { identifier, domain: self.domain, codomain: self.codomain, memoized: self.memoized }
     }
 }
 
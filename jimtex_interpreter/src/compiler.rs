@@ -0,0 +1,174 @@
+use std::ops::Range;
+
+use crate::ast_types::Statement;
+use crate::format::{FormattedNumber, Renderer};
+use crate::document::{parse_document, render_token, Argument, CodeBlock, Document, Node};
+use crate::errors::{RuntimeError, RuntimeErrorTypes};
+use crate::interpreter::ProgramInterpreter;
+use crate::lexer::{Token, TokenString};
+use crate::parser::{define_macro, is_evaluated_environment, parse_code_block, parse_math, Macros};
+use crate::parser_ast::parse_to_ast;
+
+type CompileResult = Result<(), RuntimeError>;
+
+// Turns a JimTeX document into plain LaTeX. Every code block runs in the same interpreter,
+// inline blocks are replaced with their value and display blocks are typeset as math
+pub struct Compiler {
+    interpreter: ProgramInterpreter,
+    macros:      Macros,
+    eval_math:   bool,
+    blocks:      Vec<BlockOutput>,
+}
+
+#[derive(Debug)]
+pub struct Compilation {
+    pub latex:  String,
+    // One for every code block, in the order they appear
+    pub blocks: Vec<BlockOutput>,
+    // Pragmas that were ignored
+    pub warnings: Vec<String>,
+}
+
+// What a code block evaluated to, the values of its expressions or the error that stopped it
+#[derive(Debug)]
+pub struct BlockOutput {
+    pub index:  usize,
+    pub range:  Range<usize>,
+    pub result: Result<Vec<FormattedNumber>, RuntimeError>,
+}
+
+// Errors in code blocks are kept with their block and the rest of the document still compiles,
+// only math evaluated with \jimtexeval can fail the whole document
+pub fn compile(tokens: TokenString) -> Result<Compilation, RuntimeError> {
+    let document = parse_document(tokens);
+    let mut compiler = Compiler::new(document.evaluates_math());
+    compiler.compile_document(&document)
+}
+
+impl Compiler {
+    pub fn new(eval_math: bool) -> Self {
+        Self { interpreter: ProgramInterpreter::default(), macros: Macros::new(), eval_math, blocks: vec![] }
+    }
+
+    pub fn compile_document(&mut self, document: &Document) -> Result<Compilation, RuntimeError> {
+        let mut latex = String::new();
+        self.compile_nodes(&document.nodes, &mut latex)?;
+        Ok(Compilation { latex, blocks: std::mem::take(&mut self.blocks), warnings: self.interpreter.warnings().to_vec() })
+    }
+
+    fn compile_nodes(&mut self, nodes: &[Node], output: &mut String) -> CompileResult {
+        for node in nodes {
+            match node {
+                Node::NewCommand(new_command) => {
+                    define_macro(&mut self.macros, new_command);
+                    output.push_str(&node.to_string());
+                },
+                Node::Pragma(pragma) => {
+                    self.run(vec![Token::Pragma(pragma.clone())])?;
+                    output.push_str(&node.to_string());
+                },
+                Node::Code(block) if block.display => {
+                    let result = self.run(parse_code_block(block, &self.macros));
+                    self.blocks.push(BlockOutput { index: block.index, range: block.range.clone(), result });
+                    output.push_str(&format!("\\[{}\\]", typeset_code(&block.tokens)));
+                },
+                Node::Code(block) => {
+                    let result = self.evaluate_inline(block);
+                    // Like an undefined reference in LaTeX
+                    let value = match &result {
+                        Ok(value) => value.render(Renderer::Latex),
+                        Err(_)    => "\\textbf{??}".to_owned(),
+                    };
+                    output.push_str(&format!("\\ensuremath{{{value}}}"));
+                    self.blocks.push(BlockOutput { index: block.index, range: block.range.clone(), result: result.map(|value| vec![value]) });
+                },
+                // Only means something to JimTeX
+                Node::Command(command) if command.name == "jimtexeval" => (),
+                Node::Command(command) => {
+                    output.push_str(&format!("\\{}", command.name));
+                    if command.starred {
+                        output.push('*');
+                    }
+                    self.compile_args(&command.args, output)?;
+                },
+                Node::Group(body) => {
+                    output.push('{');
+                    self.compile_nodes(body, output)?;
+                    output.push('}');
+                },
+                Node::Environment(environment) => {
+                    let evaluated = self.eval_math && is_evaluated_environment(&environment.environment);
+                    if evaluated {
+                        self.run(parse_math(&environment.body, &self.macros))?;
+                    }
+                    output.push_str(&format!("\\begin{{{}}}", environment.name));
+                    self.compile_args(&environment.args, output)?;
+                    self.compile_formula(&environment.body, evaluated, output)?;
+                    output.push_str(&format!("\\end{{{}}}", environment.name));
+                },
+                Node::Math(math) => {
+                    let evaluated = self.eval_math;
+                    if evaluated {
+                        self.run(parse_math(&math.body, &self.macros))?;
+                    }
+                    let (open, close) = math.mode.delimiters();
+                    output.push_str(open);
+                    self.compile_formula(&math.body, evaluated, output)?;
+                    output.push_str(close);
+                },
+                Node::Text(_) | Node::Comment(_) | Node::Verbatim(_) => output.push_str(&node.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    // Formulas nested in one that already ran were part of it and don't run again
+    fn compile_formula(&mut self, body: &[Node], evaluated: bool, output: &mut String) -> CompileResult {
+        let eval_math = self.eval_math;
+        self.eval_math = eval_math && !evaluated;
+        let result = self.compile_nodes(body, output);
+        self.eval_math = eval_math;
+        result
+    }
+
+    fn compile_args(&mut self, args: &[Argument], output: &mut String) -> CompileResult {
+        for arg in args {
+            let (open, close, body) = match arg {
+                Argument::Optional(body) => ('[', ']', body),
+                Argument::Required(body) => ('{', '}', body),
+            };
+            output.push(open);
+            self.compile_nodes(body, output)?;
+            output.push(close);
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, tokens: TokenString) -> Result<Vec<FormattedNumber>, RuntimeError> {
+        self.interpreter.evaluate_program(parse_to_ast(tokens))
+    }
+
+    // An inline block is one expression, anything else would have nothing to show
+    fn evaluate_inline(&mut self, block: &CodeBlock) -> Result<FormattedNumber, RuntimeError> {
+        let program = parse_to_ast(parse_code_block(block, &self.macros));
+        if !matches!(&program.program[..], [Statement::Expression(_) | Statement::FormattedExpression(..)]) {
+            return Err(RuntimeError::new(self.interpreter.line() + 1, RuntimeErrorTypes::InlineCodeNotExpression));
+        }
+        let mut values = self.interpreter.evaluate_program(program)?;
+        Ok(values.remove(0))
+    }
+}
+
+// The source of a display block is already math, except for JimTeX's own keywords
+fn typeset_code(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| match token {
+        Token::CommandStub(name) => match &name[..] {
+            "if"   => "\\text{if }".to_owned(),
+            "then" => "\\text{ then }".to_owned(),
+            "else" => "\\text{ else }".to_owned(),
+            "memo" => String::new(),
+            _      => render_token(token),
+        },
+        token => render_token(token),
+    }).collect()
+}
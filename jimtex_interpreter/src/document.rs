@@ -0,0 +1,528 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::lexer::{Operator, Token, TokenString};
+use crate::parser::{make_command_stubs, Environment, NewCommand};
+
+// The whole LaTeX document, shared by the interpreter, compiler and IDE.
+// Writing a Document back out with Display gives back the source it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    // Anything without structure, words, numbers, spaces and control symbols like \%
+    Text(TokenString),
+    Group(Vec<Node>),
+    Command(DocumentCommand),
+    Environment(DocumentEnvironment),
+    Math(Math),
+    Code(CodeBlock),
+    // Everything after the %, the newline ending it included
+    Comment(String),
+    // %jimtex: directives for the interpreter
+    Pragma(String),
+    Verbatim(Verbatim),
+    NewCommand(NewCommand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Optional(Vec<Node>),
+    Required(Vec<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentCommand {
+    pub name:    String,
+    pub starred: bool,
+    pub args:    Vec<Argument>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentEnvironment {
+    pub environment: Environment,
+    // align* and align are the same Environment, the name keeps the star
+    pub name: String,
+    pub args: Vec<Argument>,
+    pub body: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathMode {
+    // $ ... $
+    Dollar,
+    // $$ ... $$
+    DoubleDollar,
+    // \( ... \)
+    Paren,
+    // \[ ... \]
+    Bracket,
+}
+
+impl MathMode {
+    pub fn delimiters(&self) -> (&'static str, &'static str) {
+        match self {
+            MathMode::Dollar       => ("$", "$"),
+            MathMode::DoubleDollar => ("$$", "$$"),
+            MathMode::Paren        => ("\\(", "\\)"),
+            MathMode::Bracket      => ("\\[", "\\]"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Math {
+    pub mode: MathMode,
+    pub body: Vec<Node>,
+}
+
+// \$( ... \$) and \$[ ... \$], the tokens are left for the interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub display: bool,
+    pub tokens:  TokenString,
+    // Counting from 0 in the order the blocks appear
+    pub index:   usize,
+    // Bytes of the source the block takes up, delimiters included
+    pub range:   Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verbatim {
+    // \verb|text|
+    Inline { delimiter: char, text: String },
+    // \begin{verbatim} text \end{verbatim}
+    Environment { name: String, text: String },
+}
+
+// Where a run of nodes stops
+#[derive(PartialEq)]
+enum Until {
+    End,
+    RightBrace,
+    RightBracket,
+    Math(MathMode),
+    EndEnvironment(String),
+}
+
+struct DocumentParser {
+    tokens:  TokenString,
+    pos:     usize,
+    // Where each token starts in the source, with the end of the source last
+    offsets: Vec<usize>,
+    code_blocks: usize,
+}
+
+pub fn parse_document(tokens: TokenString) -> Document {
+    let tokens = make_command_stubs(tokens);
+    let mut offsets = vec![0];
+    for token in &tokens {
+        offsets.push(offsets[offsets.len() - 1] + render_token(token).len());
+    }
+    let mut parser = DocumentParser { tokens, pos: 0, offsets, code_blocks: 0 };
+    Document { nodes: parser.parse_nodes(&Until::End) }
+}
+
+impl DocumentParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // Unterminated groups and environments run to the end of the document instead of failing,
+    // the IDE has to be able to show documents while they're being written
+    fn parse_nodes(&mut self, until: &Until) -> Vec<Node> {
+        let mut nodes: Vec<Node> = vec![];
+
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::RightBrace if *until == Until::RightBrace => {
+                    self.pos += 1;
+                    return nodes;
+                },
+                Token::RightBracket if *until == Until::RightBracket => {
+                    self.pos += 1;
+                    return nodes;
+                },
+                Token::FormatDollar       if *until == Until::Math(MathMode::Dollar)       => { self.pos += 1; return nodes; },
+                Token::FormatDoubleDollar if *until == Until::Math(MathMode::DoubleDollar) => { self.pos += 1; return nodes; },
+                Token::CloseInline        if *until == Until::Math(MathMode::Paren)        => { self.pos += 1; return nodes; },
+                Token::CloseDisplay       if *until == Until::Math(MathMode::Bracket)      => { self.pos += 1; return nodes; },
+                Token::CommandStub(ref name) if name == "end" => {
+                    let name = self.environment_name(1);
+                    match (until, name) {
+                        (Until::EndEnvironment(expected), Some((name, offset))) if *expected == name => {
+                            self.pos += offset + 1;
+                            return nodes;
+                        },
+                        _ => nodes.push(self.parse_command()),
+                    }
+                },
+                Token::CommandStub(ref name) if name == "begin" => nodes.push(self.parse_environment()),
+                Token::CommandStub(ref name) if name == "verb"  => nodes.push(self.parse_inline_verbatim()),
+                Token::CommandStub(_) => nodes.push(self.parse_command()),
+                Token::LeftBrace => {
+                    self.pos += 1;
+                    nodes.push(Node::Group(self.parse_nodes(&Until::RightBrace)));
+                },
+                Token::FormatDollar       => nodes.push(self.parse_math(MathMode::Dollar)),
+                Token::FormatDoubleDollar => nodes.push(self.parse_math(MathMode::DoubleDollar)),
+                Token::OpenInline         => nodes.push(self.parse_math(MathMode::Paren)),
+                Token::OpenDisplay        => nodes.push(self.parse_math(MathMode::Bracket)),
+                Token::OpenCodeInline     => nodes.push(self.parse_code(false)),
+                Token::OpenCodeDisplay    => nodes.push(self.parse_code(true)),
+                Token::Comment(text) => {
+                    self.pos += 1;
+                    nodes.push(Node::Comment(text));
+                },
+                Token::Pragma(pragma) => {
+                    self.pos += 1;
+                    nodes.push(Node::Pragma(pragma));
+                },
+                Token::NewCommand(new_command) => {
+                    self.pos += 1;
+                    nodes.push(Node::NewCommand(new_command));
+                },
+                // Control symbols, \% \& \, ...
+                Token::Backslash => {
+                    self.pos += 1;
+                    let mut text = vec![Token::Backslash];
+                    text.extend(self.next());
+                    push_text(&mut nodes, text);
+                },
+                token => {
+                    self.pos += 1;
+                    push_text(&mut nodes, vec![token]);
+                },
+            }
+        }
+
+        nodes
+    }
+
+    // {name} starting at offset from the current token, with how many tokens it took up
+    fn environment_name(&self, offset: usize) -> Option<(String, usize)> {
+        if self.peek_at(offset) != Some(&Token::LeftBrace) {
+            return None;
+        }
+        let mut name = String::new();
+        let mut length = 1;
+        loop {
+            match self.peek_at(offset + length)? {
+                Token::RightBrace => return Some((name, offset + length)),
+                Token::Text(text) => name.push_str(text),
+                Token::Operator(Operator::Mul) => name.push('*'),
+                _ => return None,
+            }
+            length += 1;
+        }
+    }
+
+    fn parse_args(&mut self, command: &str) -> Vec<Argument> {
+        // \left[ is a delimiter, not an optional argument
+        let takes_optional = !matches!(command, "left" | "right" | "middle") && !command.starts_with("big") && !command.starts_with("Big");
+        let mut args = vec![];
+        loop {
+            match self.peek() {
+                Some(Token::LeftBrace) => {
+                    self.pos += 1;
+                    args.push(Argument::Required(self.parse_nodes(&Until::RightBrace)));
+                },
+                Some(Token::LeftBracket) if takes_optional => {
+                    self.pos += 1;
+                    args.push(Argument::Optional(self.parse_nodes(&Until::RightBracket)));
+                },
+                _ => return args,
+            }
+        }
+    }
+
+    fn parse_command(&mut self) -> Node {
+        let Some(Token::CommandStub(name)) = self.next() else {
+            unreachable!()
+        };
+        let starred = self.peek() == Some(&Token::Operator(Operator::Mul));
+        if starred {
+            self.pos += 1;
+        }
+        let args = self.parse_args(&name);
+        Node::Command(DocumentCommand { name, starred, args })
+    }
+
+    fn parse_environment(&mut self) -> Node {
+        let Some((name, offset)) = self.environment_name(1) else {
+            // \begin without a name is just a command
+            return self.parse_command();
+        };
+        self.pos += offset + 1;
+        let environment = Environment::from_name(&name);
+
+        if environment == Environment::Verbatim {
+            let mut text = String::new();
+            while self.peek().is_some() {
+                if let (Some(Token::CommandStub(end)), Some((end_name, offset))) = (self.peek(), self.environment_name(1)) {
+                    if end == "end" && end_name == name {
+                        self.pos += offset + 1;
+                        break;
+                    }
+                }
+                text.push_str(&render_token(&self.next().unwrap()));
+            }
+            return Node::Verbatim(Verbatim::Environment { name, text });
+        }
+
+        let args = self.parse_args(&name);
+        let body = self.parse_nodes(&Until::EndEnvironment(name.clone()));
+        Node::Environment(DocumentEnvironment { environment, name, args, body })
+    }
+
+    fn parse_inline_verbatim(&mut self) -> Node {
+        self.pos += 1;
+        let mut source = String::new();
+        while let Some(token) = self.next() {
+            source.push_str(&render_token(&token));
+            let mut chars = source.chars();
+            let Some(delimiter) = chars.next() else {
+                continue;
+            };
+            let rest = chars.as_str();
+            if let Some(end) = rest.find(delimiter) {
+                let text = rest[..end].to_owned();
+                // The closing delimiter can be in the middle of a word, \verb|x|y
+                let trailing = &rest[(end + delimiter.len_utf8())..];
+                if !trailing.is_empty() {
+                    self.pos -= 1;
+                    self.tokens[self.pos] = Token::Text(trailing.to_owned());
+                    self.offsets[self.pos] = self.offsets[self.pos + 1] - trailing.len();
+                }
+                return Node::Verbatim(Verbatim::Inline { delimiter, text });
+            }
+        }
+        // \verb that never ends, keep it as text
+        Node::Text(vec![Token::CommandStub("verb".to_owned()), Token::Text(source)])
+    }
+
+    fn parse_math(&mut self, mode: MathMode) -> Node {
+        self.pos += 1;
+        let body = self.parse_nodes(&Until::Math(mode.clone()));
+        Node::Math(Math { mode, body })
+    }
+
+    fn parse_code(&mut self, display: bool) -> Node {
+        let start = self.offsets[self.pos];
+        self.pos += 1;
+        let mut tokens = vec![];
+        while let Some(token) = self.next() {
+            if token == Token::CloseCodeInline || token == Token::CloseCodeDisplay {
+                break;
+            }
+            tokens.push(token);
+        }
+        let end = self.offsets[self.pos.min(self.tokens.len())];
+        let index = self.code_blocks;
+        self.code_blocks += 1;
+        Node::Code(CodeBlock { display, tokens, index, range: start..end })
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: TokenString) {
+    if let Some(Node::Text(previous)) = nodes.last_mut() {
+        previous.extend(text);
+    } else {
+        nodes.push(Node::Text(text));
+    }
+}
+
+impl Document {
+    // Every node in document order, parents before their children
+    pub fn walk<F: FnMut(&Node)>(&self, f: &mut F) {
+        walk_nodes(&self.nodes, f);
+    }
+
+    // \jimtexeval anywhere in the document makes its math code as well
+    pub fn evaluates_math(&self) -> bool {
+        let mut evaluates = false;
+        self.walk(&mut |node| {
+            if let Node::Command(command) = node {
+                evaluates |= command.name == "jimtexeval";
+            }
+        });
+        evaluates
+    }
+
+    // Code blocks in the order they appear, however deep they're nested
+    pub fn code_blocks(&self) -> Vec<&CodeBlock> {
+        let mut blocks = vec![];
+        collect_code_blocks(&self.nodes, &mut blocks);
+        blocks
+    }
+}
+
+pub(crate) fn children(node: &Node) -> Vec<&[Node]> {
+    let mut children: Vec<&[Node]> = vec![];
+    match node {
+        Node::Group(body) => children.push(body),
+        Node::Math(math)  => children.push(&math.body),
+        Node::Environment(environment) => {
+            for arg in &environment.args {
+                let (Argument::Optional(body) | Argument::Required(body)) = arg;
+                children.push(body);
+            }
+            children.push(&environment.body);
+        },
+        Node::Command(command) => {
+            for arg in &command.args {
+                let (Argument::Optional(body) | Argument::Required(body)) = arg;
+                children.push(body);
+            }
+        },
+        Node::Text(_) | Node::Code(_) | Node::Comment(_) | Node::Pragma(_) | Node::Verbatim(_) | Node::NewCommand(_) => (),
+    }
+    children
+}
+
+fn walk_nodes<F: FnMut(&Node)>(nodes: &[Node], f: &mut F) {
+    for node in nodes {
+        f(node);
+        for child in children(node) {
+            walk_nodes(child, f);
+        }
+    }
+}
+
+fn collect_code_blocks<'a>(nodes: &'a [Node], blocks: &mut Vec<&'a CodeBlock>) {
+    for node in nodes {
+        if let Node::Code(block) = node {
+            blocks.push(block);
+        }
+        for child in children(node) {
+            collect_code_blocks(child, blocks);
+        }
+    }
+}
+
+pub fn render_token(token: &Token) -> String {
+    match token {
+        Token::Tab                => "\t".to_owned(),
+        Token::Colon              => ":".to_owned(),
+        Token::Comma              => ",".to_owned(),
+        Token::Space              => " ".to_owned(),
+        Token::Dollar             => "\\$".to_owned(),
+        Token::Equals             => "=".to_owned(),
+        Token::Period             => ".".to_owned(),
+        Token::Newline            => "\n".to_owned(),
+        Token::Semicolon          => ";".to_owned(),
+        Token::Pipe               => "|".to_owned(),
+        Token::Exclamation        => "!".to_owned(),
+        Token::Percent            => "%".to_owned(),
+        Token::Exponent           => "^".to_owned(),
+        Token::Backslash          => "\\".to_owned(),
+        Token::LeftBrace          => "{".to_owned(),
+        Token::LeftParen          => "(".to_owned(),
+        Token::Subscript          => "_".to_owned(),
+        Token::Octothorpe         => "#".to_owned(),
+        Token::OpenInline         => "\\(".to_owned(),
+        Token::RightBrace         => "}".to_owned(),
+        Token::RightParen         => ")".to_owned(),
+        Token::CloseInline        => "\\)".to_owned(),
+        Token::LeftBracket        => "[".to_owned(),
+        Token::OpenDisplay        => "\\[".to_owned(),
+        Token::CloseDisplay       => "\\]".to_owned(),
+        Token::FormatDollar       => "$".to_owned(),
+        Token::RightBracket       => "]".to_owned(),
+        Token::OpenCodeInline     => "\\$(".to_owned(),
+        Token::CloseCodeInline    => "\\$)".to_owned(),
+        Token::NewlineOperator    => "\\\\".to_owned(),
+        Token::OpenCodeDisplay    => "\\$[".to_owned(),
+        Token::CloseCodeDisplay   => "\\$]".to_owned(),
+        Token::EscapedLeftBrace   => "\\{".to_owned(),
+        Token::EscapedOctothorpe  => "\\#".to_owned(),
+        Token::EscapedRightBrace  => "\\}".to_owned(),
+        Token::EscapedPercent     => "\\%".to_owned(),
+        Token::FormatDoubleDollar => "$$".to_owned(),
+        Token::Text(text)         => text.clone(),
+        Token::Number(number)     => number.clone(),
+        Token::Comment(text)      => format!("%{text}"),
+        Token::Pragma(pragma)     => format!("%jimtex:{pragma}"),
+        Token::Operator(Operator::Add) => "+".to_owned(),
+        Token::Operator(Operator::Sub) => "-".to_owned(),
+        Token::Operator(Operator::Mul) => "*".to_owned(),
+        Token::Operator(Operator::Div) => "/".to_owned(),
+        Token::CommandStub(name)  => format!("\\{name}"),
+        Token::NewCommand(new_command) => new_command.to_string(),
+        // Only lexer tokens and command stubs make it into a document
+        other => format!("{other:?}"),
+    }
+}
+
+pub fn render_tokens(tokens: &[Token]) -> String {
+    tokens.iter().map(render_token).collect()
+}
+
+fn render_nodes(f: &mut std::fmt::Formatter<'_>, nodes: &[Node]) -> std::fmt::Result {
+    nodes.iter().try_for_each(|node| write!(f, "{node}"))
+}
+
+impl Display for Argument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Argument::Optional(body) => { write!(f, "[")?; render_nodes(f, body)?; write!(f, "]") },
+            Argument::Required(body) => { write!(f, "{{")?; render_nodes(f, body)?; write!(f, "}}") },
+        }
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Node::Text(tokens)   => write!(f, "{}", render_tokens(tokens)),
+            Node::Group(body)    => { write!(f, "{{")?; render_nodes(f, body)?; write!(f, "}}") },
+            Node::Comment(text)  => write!(f, "%{text}"),
+            Node::Pragma(pragma) => write!(f, "%jimtex:{pragma}"),
+            Node::NewCommand(new_command) => write!(f, "{new_command}"),
+            Node::Command(command) => {
+                write!(f, "\\{}", command.name)?;
+                if command.starred {
+                    write!(f, "*")?;
+                }
+                command.args.iter().try_for_each(|arg| write!(f, "{arg}"))
+            },
+            Node::Environment(environment) => {
+                write!(f, "\\begin{{{}}}", environment.name)?;
+                environment.args.iter().try_for_each(|arg| write!(f, "{arg}"))?;
+                render_nodes(f, &environment.body)?;
+                write!(f, "\\end{{{}}}", environment.name)
+            },
+            Node::Math(math) => {
+                let (open, close) = math.mode.delimiters();
+                write!(f, "{open}")?;
+                render_nodes(f, &math.body)?;
+                write!(f, "{close}")
+            },
+            Node::Code(block) => {
+                let (open, close) = if block.display { ("\\$[", "\\$]") } else { ("\\$(", "\\$)") };
+                write!(f, "{open}{}{close}", render_tokens(&block.tokens))
+            },
+            Node::Verbatim(Verbatim::Inline { delimiter, text }) => write!(f, "\\verb{delimiter}{text}{delimiter}"),
+            Node::Verbatim(Verbatim::Environment { name, text }) => write!(f, "\\begin{{{name}}}{text}\\end{{{name}}}"),
+        }
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        render_nodes(f, &self.nodes)
+    }
+}
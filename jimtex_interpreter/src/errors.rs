@@ -1,6 +1,41 @@
 use std::fmt::Display;
 
-use crate::ast_types::Identifier;
+use num::BigInt;
+
+use crate::algebra::{AxiomFailure, ORDER_LIMIT};
+use crate::ast::Builtin;
+use crate::ast_types::{Identifier, Number};
+
+// Found before anything runs, reported once the statement it's in is reached
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    MacroTooDeep(String),
+    MissingMacroArguments(String),
+    // The macro and the #n in its definition
+    InvalidMacroParameter(String, usize),
+    UnknownFormat(String),
+    // \{1, 2\} outside of (\{1, 2\}, +)
+    BareSet,
+    // An unmatched |, \lfloor, \rceil or \langle
+    UnbalancedDelimiter,
+    UnbalancedBrackets,
+    // x_ or \log_ at the end of a line
+    MissingSubscript,
+    // Nothing where a value should be, \max()
+    MissingValue,
+    // \int_ at the end of a line
+    IntegralWithoutBound,
+    IntegralWithoutDifferential,
+    // \int_0 x \, dx, a definite integral needs both
+    IntegralWithOneBound,
+    // \lim x, or \lim_{x} x with no \to
+    LimitWithoutSubscript,
+    LimitWithoutApproach,
+    // \begin{pmatrix} with no \end
+    UnclosedEnvironment(String),
+    // \langle a \rangle, the group it's a subgroup of goes in the subscript
+    GeneratedWithoutGroup,
+}
 
 #[derive(Debug)]
 pub struct RuntimeError {
@@ -34,6 +69,34 @@ pub enum RuntimeErrorTypes {
     UseBeforeDefinition,
     FunctionDefinedWithNoDeclaration,
     ConditionalsMustEvaluateToNumber,
+    MissingSequence(Identifier),
+    MissingSequenceTerm(Identifier, BigInt),
+    InvalidSequenceIndex(Number),
+    InlineCodeNotExpression,
+    InvalidFactorial(Number),
+    WrongNumberOfArguments(Builtin, usize),
+    ShadowedConstant(Identifier),
+    NotDifferentiable(Identifier),
+    NotIntegrable(Identifier),
+    // Why, or how the numerical evaluation went
+    LimitDoesNotExist(String),
+    DimensionMismatch,
+    NotSquare,
+    SingularMatrix,
+    NotPolynomial(Identifier),
+    // The leading coefficient of the divisor isn't invertible, or the quotient isn't in the ring
+    NotDivisible,
+    NoRoots,
+    NotInvertible(BigInt, BigInt),
+    AxiomFails(AxiomFailure),
+    InfiniteCarrier,
+    TooManyElements(usize),
+    NotAnElement(Number),
+    NotASubgroup,
+    // The subgroups share more than the identity or don't commute
+    NotInternalProduct,
+    ParseError(ParseError),
+    Undefined,
 }
 
 impl Display for RuntimeErrorTypes {
@@ -42,9 +105,57 @@ impl Display for RuntimeErrorTypes {
             RuntimeErrorTypes::TypeError                        => write!(f, "Type Error"),
             RuntimeErrorTypes::UseBeforeDefinition              => write!(f, "Use Before Definition"),
             RuntimeErrorTypes::FunctionDefinedWithNoDeclaration => write!(f, "Function Defined with no Declaration"),
-            RuntimeErrorTypes::MissingVariable(ident)           => write!(f, "Variable {ident:?} does not exist"),
-            RuntimeErrorTypes::MissingFunction(ident)           => write!(f, "Function {ident:?} does not exist"),
+            RuntimeErrorTypes::MissingVariable(ident)           => write!(f, "Variable {ident} does not exist"),
+            RuntimeErrorTypes::MissingFunction(ident)           => write!(f, "Function {ident} does not exist"),
             RuntimeErrorTypes::ConditionalsMustEvaluateToNumber => write!(f, "Conditionals must evaluate to a number"),
+            RuntimeErrorTypes::MissingSequence(ident)           => write!(f, "Sequence {ident} does not exist"),
+            RuntimeErrorTypes::MissingSequenceTerm(ident, n)    => write!(f, "Sequence {ident} has no base case or general term for index {n}"),
+            RuntimeErrorTypes::InvalidSequenceIndex(index)      => write!(f, "Sequence index {index} must be a non-negative integer"),
+            RuntimeErrorTypes::InlineCodeNotExpression          => write!(f, "Inline code must be a single expression"),
+            RuntimeErrorTypes::InvalidFactorial(number)         => write!(f, "Factorial of {number} is undefined, it must be a non-negative integer"),
+            RuntimeErrorTypes::WrongNumberOfArguments(function, n) => write!(f, "{function} can't take {n} arguments"),
+            RuntimeErrorTypes::Undefined                        => write!(f, "Result is undefined"),
+            RuntimeErrorTypes::ShadowedConstant(ident)          => write!(f, "{ident} is a constant and can't be redefined in strict mode"),
+            RuntimeErrorTypes::NotDifferentiable(ident)         => write!(f, "Can't differentiate with respect to {ident}"),
+            RuntimeErrorTypes::NotIntegrable(ident)             => write!(f, "Can't find an antiderivative with respect to {ident}"),
+            RuntimeErrorTypes::LimitDoesNotExist(reason)        => write!(f, "Limit does not exist, {reason}"),
+            RuntimeErrorTypes::DimensionMismatch                => write!(f, "Matrix dimensions don't match"),
+            RuntimeErrorTypes::NotSquare                        => write!(f, "Matrix must be square"),
+            RuntimeErrorTypes::SingularMatrix                   => write!(f, "Matrix is singular and has no inverse"),
+            RuntimeErrorTypes::NotPolynomial(ident)             => write!(f, "{ident} is not a polynomial in one variable"),
+            RuntimeErrorTypes::NotDivisible                     => write!(f, "Can't divide with remainder by that polynomial in its ring"),
+            RuntimeErrorTypes::NoRoots                          => write!(f, "Polynomial has no roots"),
+            RuntimeErrorTypes::NotInvertible(a, n)              => write!(f, "{a} has no inverse modulo {n}"),
+            RuntimeErrorTypes::AxiomFails(failure)              => write!(f, "Not a group or ring, {failure}"),
+            RuntimeErrorTypes::InfiniteCarrier                  => write!(f, "A group or ring needs a finite set of elements"),
+            RuntimeErrorTypes::TooManyElements(order)           => write!(f, "{order} elements is more than the {ORDER_LIMIT} the axioms are checked for"),
+            RuntimeErrorTypes::NotAnElement(number)             => write!(f, "{number} isn't an element of the group"),
+            RuntimeErrorTypes::NotASubgroup                     => write!(f, "Not a subgroup"),
+            RuntimeErrorTypes::NotInternalProduct               => write!(f, "Not an internal direct product, the subgroups have to commute and only share the identity"),
+            RuntimeErrorTypes::ParseError(error)                => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MacroTooDeep(name)          => write!(f, "Expanding \\{name} goes too deep, is a macro defined in terms of itself?"),
+            ParseError::MissingMacroArguments(name) => write!(f, "Macro \\{name} is missing arguments"),
+            ParseError::InvalidMacroParameter(name, param) => write!(f, "Macro \\{name} has no argument #{param}"),
+            ParseError::UnknownFormat(option)       => write!(f, "Unknown format {option}"),
+            ParseError::UnbalancedDelimiter         => write!(f, "Unbalanced delimiter, every |, \\lfloor, \\lceil and \\langle needs a match"),
+            ParseError::UnbalancedBrackets          => write!(f, "Unbalanced brackets"),
+            ParseError::MissingSubscript            => write!(f, "Subscript with no index"),
+            ParseError::MissingValue                => write!(f, "Missing a value"),
+            ParseError::IntegralWithoutBound        => write!(f, "Integral with no bound"),
+            ParseError::IntegralWithoutDifferential => write!(f, "Integral with no differential"),
+            ParseError::IntegralWithOneBound        => write!(f, "Integral with only one bound"),
+            ParseError::LimitWithoutSubscript       => write!(f, "Limit with no subscript"),
+            ParseError::LimitWithoutApproach        => write!(f, "Limit with no \\to"),
+            ParseError::UnclosedEnvironment(name)   => write!(f, "\\begin{{{name}}} with no \\end"),
+            ParseError::GeneratedWithoutGroup       => write!(f, "\\langle \\rangle without the group as a subscript"),
+            ParseError::BareSet                     => write!(f, "A set is only a value as the elements of a group or ring, like (\\{{1, 2\\}}, +)"),
         }
     }
 }
@@ -0,0 +1,222 @@
+use std::fmt::Display;
+
+use num::bigint::Sign;
+use num::{BigInt, Signed};
+
+use crate::ast_types::Number;
+use crate::real::{Real, RealContext};
+
+// How numbers are written out, set for a document with %jimtex: pragmas or
+// for one expression with \jimtexformat{scientific 3}{x}
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NumberFormat {
+    pub notation:  Notation,
+    pub fractions: Fractions,
+    pub grouping:  Grouping,
+    // Digits and rounding of the document, for reals that have to be cut short
+    pub context:   RealContext,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Notation {
+    // Every digit of a real
+    #[default]
+    Exact,
+    // Digits after the decimal point, %jimtex: precision 3 is the same as %jimtex: format fixed 3
+    Fixed(usize),
+    // Significant digits, 1.23e5 or 1.23 \times 10^{5}
+    Scientific(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Fractions {
+    // 7/2 or \frac{7}{2}
+    #[default]
+    Improper,
+    // 3 1/2 or 3\frac{1}{2}
+    Mixed,
+    // 3.5
+    Decimal,
+}
+
+// Thousands separators, 1,000,000 or 1 000 000
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Grouping {
+    #[default]
+    None,
+    Comma,
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    Plain,
+    Latex,
+}
+
+// One part of a format, what a pragma or \jimtexformat changes
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatOption {
+    Notation(Notation),
+    Fractions(Fractions),
+    Grouping(Grouping),
+}
+
+// The value of an expression, with the format it was evaluated under
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormattedNumber {
+    pub number: Number,
+    pub format: NumberFormat,
+}
+
+// fixed 2, scientific 4, mixed, comma
+pub fn parse_format_option(option: &str) -> Option<FormatOption> {
+    Some(match option.split_whitespace().collect::<Vec<_>>()[..] {
+        ["exact"]                => FormatOption::Notation(Notation::Exact),
+        ["fixed", places]        => FormatOption::Notation(Notation::Fixed(places.parse().ok()?)),
+        ["scientific", digits]   => FormatOption::Notation(Notation::Scientific(digits.parse().ok().filter(|digits| *digits > 0)?)),
+        ["improper"]             => FormatOption::Fractions(Fractions::Improper),
+        ["mixed"]                => FormatOption::Fractions(Fractions::Mixed),
+        ["decimal"]              => FormatOption::Fractions(Fractions::Decimal),
+        ["thousands", "none"]    => FormatOption::Grouping(Grouping::None),
+        ["thousands", "comma"]   => FormatOption::Grouping(Grouping::Comma),
+        ["thousands", "space"]   => FormatOption::Grouping(Grouping::Space),
+        _ => return None,
+    })
+}
+
+impl NumberFormat {
+    pub fn apply(&mut self, option: FormatOption) {
+        match option {
+            FormatOption::Notation(notation)   => self.notation  = notation,
+            FormatOption::Fractions(fractions) => self.fractions = fractions,
+            FormatOption::Grouping(grouping)   => self.grouping  = grouping,
+        }
+    }
+
+    pub fn render(&self, number: &Number, renderer: Renderer) -> String {
+        match number {
+            Number::Integer(int) => match self.notation {
+                Notation::Scientific(digits) => self.scientific(&Real::from(int.clone()), digits, renderer),
+                _ => self.group(&int.to_string(), renderer),
+            },
+            Number::Real(real) => self.real(real, renderer),
+            Number::Rational(rat) => {
+                let (numer, denom) = (rat.numer(), rat.denom());
+                let sign = if numer.is_negative() { "-" } else { "" };
+                match self.fractions {
+                    Fractions::Decimal => self.real(&Real::from(numer.clone()).div(&Real::from(denom.clone()), &self.context), renderer),
+                    Fractions::Mixed if numer.abs() > *denom => {
+                        let (whole, part) = (numer.abs() / denom, numer.abs() % denom);
+                        let separator = if renderer == Renderer::Plain { " " } else { "" };
+                        format!("{sign}{}{separator}{}", self.group(&whole.to_string(), renderer), self.fraction(&part, denom, renderer))
+                    },
+                    _ => format!("{sign}{}", self.fraction(&numer.abs(), denom, renderer)),
+                }
+            },
+            // a + bi, leaving out whichever part is zero
+            Number::Complex(cplx) => {
+                let part = |part: f64| Real::from_f64(part).map(|part| self.real(&part, renderer)).unwrap_or(format!("{part}"));
+                let imaginary = if cplx.imag.abs() == 1.0 { "i".to_owned() } else { format!("{}i", part(cplx.imag.abs())) };
+                let sign = if cplx.imag < 0.0 { "-" } else { "+" };
+                if cplx.imag == 0.0 {
+                    part(cplx.real)
+                } else if cplx.real == 0.0 {
+                    format!("{}{imaginary}", if cplx.imag < 0.0 { "-" } else { "" })
+                } else {
+                    format!("{} {sign} {imaginary}", part(cplx.real))
+                }
+            },
+            Number::PiMultiple(coefficient) => {
+                let numer = match coefficient.numer().to_string().as_str() {
+                    "1"   => "\\pi".to_owned(),
+                    "-1"  => "-\\pi".to_owned(),
+                    numer => format!("{numer}\\pi"),
+                };
+                match (coefficient.is_integer(), renderer) {
+                    (true, _) => numer,
+                    (false, Renderer::Plain) => format!("{numer}/{}", coefficient.denom()),
+                    (false, Renderer::Latex) => match numer.strip_prefix('-') {
+                        Some(numer) => format!("-\\frac{{{numer}}}{{{}}}", coefficient.denom()),
+                        None        => format!("\\frac{{{numer}}}{{{}}}", coefficient.denom()),
+                    },
+                }
+            },
+            Number::Infinity(Sign::Minus) => "-\\infty".to_owned(),
+            Number::Infinity(_)           => "\\infty".to_owned(),
+            // Already LaTeX, and as plain as it gets
+            Number::Symbolic(symbolic)    => symbolic.to_string(),
+            Number::Polynomial(polynomial) => polynomial.to_string(),
+            // Entries in the same format, a vector is a single column
+            Number::Matrix(matrix) => {
+                let rows = (0..matrix.rows)
+                    .map(|row| matrix.row(row).iter().map(|entry| self.render(entry, renderer)).collect::<Vec<_>>().join(" & "))
+                    .collect::<Vec<_>>();
+                format!("\\begin{{pmatrix}} {} \\end{{pmatrix}}", rows.join(" \\\\ "))
+            },
+            Number::Tuple(elements) => format!("({})", elements.iter().map(|element| self.render(element, renderer)).collect::<Vec<_>>().join(", ")),
+            Number::Set(set) => format!("\\{{{}\\}}", set.elements().map(|element| self.render(element, renderer)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    fn real(&self, real: &Real, renderer: Renderer) -> String {
+        let digits = match self.notation {
+            Notation::Exact          => real.to_string(),
+            Notation::Fixed(places)  => real.to_fixed(places, self.context.rounding),
+            Notation::Scientific(digits) => return self.scientific(real, digits, renderer),
+        };
+        match digits.split_once('.') {
+            Some((integer, fraction)) => format!("{}.{fraction}", self.group(integer, renderer)),
+            None => self.group(&digits, renderer),
+        }
+    }
+
+    fn scientific(&self, real: &Real, digits: usize, renderer: Renderer) -> String {
+        let (mantissa, exponent) = real.to_scientific(digits, self.context.rounding);
+        let mantissa = mantissa.to_fixed(digits - 1, self.context.rounding);
+        match renderer {
+            Renderer::Plain => format!("{mantissa}e{exponent}"),
+            Renderer::Latex => format!("{mantissa} \\times 10^{{{exponent}}}"),
+        }
+    }
+
+    fn fraction(&self, numer: &BigInt, denom: &BigInt, renderer: Renderer) -> String {
+        let (numer, denom) = (self.group(&numer.to_string(), renderer), self.group(&denom.to_string(), renderer));
+        match renderer {
+            Renderer::Plain => format!("{numer}/{denom}"),
+            Renderer::Latex => format!("\\frac{{{numer}}}{{{denom}}}"),
+        }
+    }
+
+    // Separators every three digits of an integer part, which may have a sign
+    fn group(&self, integer: &str, renderer: Renderer) -> String {
+        let separator = match (&self.grouping, renderer) {
+            (Grouping::None, _) => return integer.to_owned(),
+            (Grouping::Comma, Renderer::Plain) => ",",
+            (Grouping::Comma, Renderer::Latex) => "{,}",
+            (Grouping::Space, Renderer::Plain) => " ",
+            (Grouping::Space, Renderer::Latex) => "\\,",
+        };
+        let (sign, digits) = integer.split_at(if integer.starts_with('-') { 1 } else { 0 });
+        let mut grouped = String::from(sign);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push_str(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
+
+impl FormattedNumber {
+    pub fn render(&self, renderer: Renderer) -> String {
+        self.format.render(&self.number, renderer)
+    }
+}
+
+impl Display for FormattedNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(Renderer::Plain))
+    }
+}
@@ -1,39 +1,121 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::f64::consts::PI;
+use std::rc::Rc;
 
-use num::BigInt;
+use num::bigint::Sign;
+use num::{BigInt, BigRational, Integer, Signed, ToPrimitive, Zero};
 
+use crate::algebra::{self, Structure, Subset};
 use crate::ast_types::*;
 use crate::errors::*;
-use crate::ast::{UnOps, BinOps};
+use crate::ast::{UnOps, BinOps, Builtin, GreekLetters};
+use crate::format::{FormatOption, FormattedNumber, Notation, NumberFormat};
 use crate::lexer::Token;
+use crate::limit::{self, Approach, ConvergenceReport};
+use crate::matrix::Matrix;
+use crate::modular;
+use crate::polynomial::{Polynomial, Ring};
+use crate::real::{Real, RealContext};
+use crate::symbolic::{self, Symbolic};
 
 type ExecutionResult           = Result<(), RuntimeError>;
-type ExecutionResultPrint      = Result<String, RuntimeError>;
+type ExecutionResultStatement  = Result<Option<FormattedNumber>, RuntimeError>;
 type ExecutionResultValue      = Result<Value, RuntimeError>;
 type ExecutionResultNumber     = Result<Number, RuntimeError>;
 type ExecutionResultExpression = Result<Expression, RuntimeError>;
 
-#[derive(Debug, Default)]
+// Shared between a function and every scope it is called in, so recursive calls hit the same cache
+type MemoTable = Rc<RefCell<HashMap<Vec<Number>, Number>>>;
+
+// Terms are computed lazily, the cache is shared the same way as a MemoTable
+#[derive(Debug, Clone, Default)]
+struct Sequence {
+    index:        Option<Identifier>,
+    general_term: Option<Expression>,
+    base_cases:   HashMap<BigInt, Number>,
+    terms:        Rc<RefCell<HashMap<BigInt, Number>>>,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ProgramInterpreter {
     function_definitions:  HashMap<Identifier, FunctionDefinition>,
     function_declarations: HashMap<Identifier, FunctionDeclaration>,
     variables: HashMap<Identifier, Number>,
+    memo_tables: HashMap<Identifier, MemoTable>,
+    sequences: HashMap<Identifier, Sequence>,
     line: usize,
+    format: NumberFormat,
+    strict: bool,
+    exact: bool,
+    symbolic: bool,
+    context: RealContext,
+    // Inside \Z/n\Z, integer arithmetic wraps
+    modulus: Option<BigInt>,
+    // How each numerical limit got its value, shared with every scope cloned from this one
+    convergence_reports: Rc<RefCell<Vec<ConvergenceReport>>>,
+    // Pragmas that were ignored
+    warnings: Vec<String>,
 }
 
 impl ProgramInterpreter {
     pub fn interpret_statement(&mut self, statement: Statement) -> ExecutionResult {
+        if let Some(number) = self.evaluate_statement(statement)? {
+            println!("{number}");
+        }
+        for report in self.convergence_reports.borrow_mut().drain(..) {
+            println!("    {report}");
+        }
+        for warning in self.warnings.drain(..) {
+            eprintln!("{warning}");
+        }
+        Ok(())
+    }
+
+    // Everything ignored so far that interpret_statement hasn't printed
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    // Every numerical limit evaluated so far, in order
+    pub fn convergence_reports(&self) -> Vec<ConvergenceReport> {
+        self.convergence_reports.borrow().clone()
+    }
+
+    // Expressions give back their value instead of printing it
+    pub fn evaluate_statement(&mut self, statement: Statement) -> ExecutionResultStatement {
         match statement {
-            Statement::FunctionDefinition(function_definition) => self.interpret_function_definition(function_definition),
-            Statement::Declaration(declaration)                => self.interpret_declaration(declaration),
-            Statement::Expression(expression)                  => {
-                match self.interpret_expression(expression) {
-                    Ok(string) => { println!("{string}"); Ok(()) },
-                    Err(error) => Err(error),
+            Statement::FunctionDefinition(function_definition) => self.interpret_function_definition(function_definition)?,
+            Statement::SequenceDefinition(sequence_definition) => self.interpret_sequence_definition(sequence_definition)?,
+            Statement::Declaration(declaration)                => self.interpret_declaration(declaration)?,
+            Statement::Pragma(pragma)                          => {
+                match pragma {
+                    Pragma::Precision(digits) => self.format.apply(FormatOption::Notation(Notation::Fixed(digits))),
+                    Pragma::Format(option)    => self.format.apply(option),
+                    Pragma::Strict(strict)    => self.strict = strict,
+                    Pragma::Exact(exact)      => self.exact = exact,
+                    Pragma::Symbolic(symbolic) => self.symbolic = symbolic,
+                    Pragma::Digits(digits)    => self.context.digits = digits,
+                    Pragma::Rounding(rounding) => self.context.rounding = rounding,
+                    Pragma::MultiLetter(_)    => (),
+                    Pragma::Unknown(pragma)   => self.warnings.push(format!("Unknown pragma %jimtex: {pragma} on line: {} is ignored", self.line)),
                 }
-            }
+            },
+            Statement::Expression(expression)                  => return Ok(Some(self.format(self.evaluate_expression(expression)?, vec![]))),
+            Statement::FormattedExpression(expression, options) => return Ok(Some(self.format(self.evaluate_expression(expression)?, options))),
+            Statement::Invalid(error)                          => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::ParseError(error))),
         }
+        Ok(None)
+    }
+
+    // The document's format, with what \jimtexformat asked for on top
+    fn format(&self, number: Number, options: Vec<FormatOption>) -> FormattedNumber {
+        let mut format = NumberFormat { context: self.context.clone(), ..self.format.clone() };
+        for option in options {
+            format.apply(option);
+        }
+        FormattedNumber { number, format }
     }
 
     // Runtime determines how to handle errors
@@ -46,12 +128,33 @@ impl ProgramInterpreter {
         Ok(())
     }
 
+    // The values of every expression in the program, in order
+    pub fn evaluate_program(&mut self, program: Program) -> Result<Vec<FormattedNumber>, RuntimeError> {
+        let mut values = vec![];
+        for statement in program {
+            self.line += 1;
+            values.extend(self.evaluate_statement(statement)?);
+        }
+        Ok(values)
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
     fn interpret_function_definition(&mut self, definition: FunctionDefinition) -> ExecutionResult {
         // NOTE: All identifiers except ones passed as an argument must become values
         // NOTE(args): Domain is only one value, so it will be assumed all inputs are of this one
         // type (We'll say for... uh... type safety)
+        if self.strict && !self.function_declarations.contains_key(&definition.identifier) {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::FunctionDefinedWithNoDeclaration));
+        }
         if !self.function_declarations.contains_key(&definition.identifier) {
-            self.function_declarations.insert(definition.identifier.clone(), FunctionDeclaration { identifier: definition.identifier.clone(), domain: Token::Any, codomain: Token::Any });
+            self.function_declarations.insert(definition.identifier.clone(), FunctionDeclaration { identifier: definition.identifier.clone(), domain: Token::Any, codomain: Token::Any, memoized: false });
+        }
+        // Redefining a memoized function invalidates what was cached for the old body
+        if self.memo_tables.contains_key(&definition.identifier) {
+            self.memo_tables.insert(definition.identifier.clone(), MemoTable::default());
         }
 
         let mut ignore = definition.arguments.clone();
@@ -61,12 +164,30 @@ impl ProgramInterpreter {
         Ok(())
     }
 
+    fn interpret_sequence_definition(&mut self, definition: SequenceDefinition) -> ExecutionResult {
+        let ignore = vec![definition.index.clone(), definition.identifier.clone()];
+        let expression = self.condense_expression(&ignore, definition.expression)?;
+        let sequence = self.sequences.entry(definition.identifier).or_default();
+        sequence.index        = Some(definition.index);
+        sequence.general_term = Some(expression);
+        sequence.terms        = Default::default();
+        Ok(())
+    }
+
+    fn is_ignored(ignore: &[Identifier], ident: &Identifier) -> bool {
+        match ident {
+            // a_n and a_{n-1} inside the definition of a
+            Identifier::SubScriptIdent(subscript) => ignore.contains(ident) || ignore.contains(&subscript.first_ident),
+            ident => ignore.contains(ident),
+        }
+    }
+
     fn condense_value(&self, ignore: &Vec<Identifier>, value: Value) -> ExecutionResultValue {
         match value {
             Value::Number(num)       => Ok(Value::Number(num)),
             Value::Expression(exp)   => Ok(Value::Expression(Box::new(self.condense_expression(ignore, *exp)?))),
             Value::Identifier(ident) => {
-                if ignore.contains(&ident.clone()) {
+                if Self::is_ignored(ignore, &ident) {
                     Ok(Value::Identifier(ident))
                 } else {
                     Ok(Value::Number(self.get_ident_val(ident)?))
@@ -93,12 +214,21 @@ impl ProgramInterpreter {
                     Ok(Expression::Value(Box::new(Value::Number(self.interpret_function_call(fn_call)?))))
                 }
             },
+            Expression::BuiltinCall(call) => {
+                // So is the variable of a derivative or integral
+                let mut ignore = ignore.clone();
+                if let (Builtin::Derivative | Builtin::Integral, Some(Value::Identifier(variable))) = (&call.function, call.args.get(1)) {
+                    ignore.push(variable.clone());
+                }
+                let args = call.args.into_iter().map(|value| self.condense_value(&ignore, value)).collect::<Result<_, _>>()?;
+                Ok(Expression::BuiltinCall(BuiltinCall { function: call.function, args }))
+            },
             Expression::Value(value) => {
                 match *value {
                     Value::Number(num) => Ok(Expression::Value(Box::new(Value::Number(num)))),
                     Value::Expression(exp) => Ok(self.condense_expression(ignore, *exp)?),
                     Value::Identifier(ident) => {
-                        if ignore.contains(&ident.clone()) {
+                        if Self::is_ignored(ignore, &ident) {
                             Ok(Expression::Value(Box::new(Value::Identifier(ident))))
                         } else {
                             Ok(Expression::Value(Box::new(Value::Number(self.get_ident_val(ident)?))))
@@ -106,6 +236,35 @@ impl ProgramInterpreter {
                     }
                 }
             },
+            Expression::SequenceTerm(term) => {
+                let index = self.condense_value(ignore, term.index)?;
+                if ignore.contains(&term.sequence) {
+                    Ok(Expression::SequenceTerm(SequenceTerm { sequence: term.sequence, index }))
+                } else {
+                    Ok(Expression::Value(Box::new(Value::Number(self.evaluate_sequence_term(&term.sequence, self.evaluate_value(index)?)?))))
+                }
+            },
+            // The variable of the limit is bound by it
+            Expression::Limit(limit) => {
+                let mut ignore = ignore.clone();
+                ignore.push(limit.variable.clone());
+                let approaches = self.condense_value(&ignore, limit.approaches)?;
+                let value = self.condense_value(&ignore, limit.value)?;
+                Ok(Expression::Limit(Limit { approaches, value, ..limit }))
+            },
+            Expression::Matrix(rows) => {
+                let rows = rows.into_iter()
+                    .map(|row| row.into_iter().map(|value| self.condense_value(ignore, value)).collect::<Result<Vec<_>, _>>())
+                    .collect::<Result<_, _>>()?;
+                Ok(Expression::Matrix(rows))
+            },
+            // A definition that couldn't be parsed fails when it's made, not when it's called
+            Expression::Invalid(error) => Err(RuntimeError::new(self.line, RuntimeErrorTypes::ParseError(error))),
+            Expression::Tuple(values) => {
+                let values = values.into_iter().map(|value| self.condense_value(ignore, value)).collect::<Result<_, _>>()?;
+                Ok(Expression::Tuple(values))
+            },
+            Expression::Structure(literal) => Ok(Expression::Structure(literal)),
             Expression::Conditional(conditional) => {
                 Ok(Expression::Conditional(Conditional { 
                     condition:  conditional.condition, 
@@ -117,9 +276,12 @@ impl ProgramInterpreter {
     }
 
     fn interpret_function_call(&self, function_call: FunctionCall) -> ExecutionResultNumber {
-        let mut function_scope = ProgramInterpreter::default();
+        let mut function_scope = ProgramInterpreter { context: self.context.clone(), symbolic: self.symbolic, ..Default::default() };
+        function_scope.modulus = self.function_modulus(&function_call.function)?;
         let function_defin = self.function_definitions.get(&function_call.function.clone()).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::MissingFunction(function_call.function.clone())))?;
-        for (ident, value) in function_defin.arguments.clone().into_iter().zip(function_call.args.clone().into_iter()) {
+        // Only calls where every argument is a number can be memoized
+        let mut memo_key: Option<Vec<Number>> = Some(vec![]);
+        for (ident, value) in function_defin.arguments.clone().into_iter().zip(function_call.args.clone()) {
             // b(n) = \if n \then n * b(n-1) \else 1,
             // b(7),
             // x(a, f) = a * f(a),
@@ -127,48 +289,113 @@ impl ProgramInterpreter {
             // When the argument passed (value) matches an identifier, insert it's definition and
             // declaration with the new ident
             match &value {
-                Value::Identifier(function_ident) => {
-                    if self.function_declarations.contains_key(function_ident) {
-                        function_scope.function_declarations.insert(ident.clone(), self.function_declarations.get(&function_ident.clone()).unwrap().clone().set_name(ident.clone()));
-                        function_scope.function_definitions.insert(ident.clone(), self.function_definitions.get(&function_ident.clone()).unwrap().clone().set_ident(ident.clone()));
-                    } else {
-                        function_scope.variables.insert(ident.clone(), self.evaluate_value(value)?);
+                Value::Identifier(function_ident) if self.function_declarations.contains_key(function_ident) => {
+                    function_scope.function_declarations.insert(ident.clone(), self.function_declarations.get(&function_ident.clone()).unwrap().clone().set_name(ident.clone()));
+                    function_scope.function_definitions.insert(ident.clone(), self.function_definitions.get(&function_ident.clone()).unwrap().clone().set_ident(ident.clone()));
+                    if let Some(table) = self.memo_tables.get(function_ident) {
+                        function_scope.memo_tables.insert(ident.clone(), Rc::clone(table));
                     }
+                    memo_key = None;
                 },
-                _ => { function_scope.variables.insert(ident, self.evaluate_value(value)?); }
+                _ => {
+                    let number = function_scope.wrap(self.evaluate_value(value)?);
+                    if let Some(key) = memo_key.as_mut() {
+                        key.push(number.clone());
+                    }
+                    function_scope.variables.insert(ident, number);
+                }
             }
         }
+
+        let memo_table = self.memo_tables.get(&function_call.function).filter(|_| memo_key.is_some());
+        if let (Some(table), Some(key)) = (memo_table, &memo_key) {
+            if let Some(number) = table.borrow().get(key) {
+                return Ok(number.clone());
+            }
+        }
+
         // Insert function into it's own scope
         function_scope.function_declarations.insert(function_call.function.clone(), self.function_declarations.get(&function_call.function.clone()).unwrap().clone());
         function_scope.function_definitions.insert(function_call.function.clone(), self.function_definitions.get(&function_call.function.clone()).unwrap().clone());
+        if let Some(table) = memo_table {
+            function_scope.memo_tables.insert(function_call.function.clone(), Rc::clone(table));
+        }
 
-        function_scope.evaluate_expression(function_defin.expression.clone()).map_err(|error| error.change_line(self.line))
+        let number = function_scope.evaluate_expression(function_defin.expression.clone()).map_err(|error| error.change_line(self.line))?;
+        let number = function_scope.wrap(number);
+        if let (Some(table), Some(key)) = (memo_table, memo_key) {
+            table.borrow_mut().insert(key, number.clone());
+        }
+        Ok(number)
+    }
+
+    fn evaluate_sequence_term(&self, identifier: &Identifier, index: Number) -> ExecutionResultNumber {
+        let sequence = self.sequences.get(identifier).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::MissingSequence(identifier.clone())))?;
+        let Number::Integer(index) = index else {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::InvalidSequenceIndex(index)));
+        };
+
+        if let Some(number) = sequence.base_cases.get(&index) {
+            return Ok(number.clone());
+        }
+        if let Some(number) = sequence.terms.borrow().get(&index) {
+            return Ok(number.clone());
+        }
+        // Anything below the base cases would recurse forever
+        if index < BigInt::from(0u8) {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::InvalidSequenceIndex(Number::Integer(index))));
+        }
+        let (Some(index_ident), Some(general_term)) = (&sequence.index, &sequence.general_term) else {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::MissingSequenceTerm(identifier.clone(), index)));
+        };
+
+        let mut sequence_scope = ProgramInterpreter { context: self.context.clone(), symbolic: self.symbolic, ..Default::default() };
+        sequence_scope.sequences.insert(identifier.clone(), sequence.clone());
+        sequence_scope.variables.insert(index_ident.clone(), Number::Integer(index.clone()));
+
+        let number = sequence_scope.evaluate_expression(general_term.clone()).map_err(|error| error.change_line(self.line))?;
+        sequence.terms.borrow_mut().insert(index, number.clone());
+        Ok(number)
     }
 
     fn interpret_declaration(&mut self, declaration: Declaration) -> ExecutionResult {
         match declaration {
             Declaration::SetDeclaration(_) => todo!(),
             Declaration::ValueDeclaration(value_declaration)       => self.interpret_value_declaration(value_declaration),
-            Declaration::FunctionDeclaration(function_declaration) => self.interpret_funct_declaration(function_declaration),
+            Declaration::FunctionDeclaration(function_declaration) => self.interpret_funct_declaration(*function_declaration),
         }
     }
 
     fn interpret_funct_declaration(&mut self, function_declaration: FunctionDeclaration) -> ExecutionResult {
+        if function_declaration.memoized {
+            self.memo_tables.insert(function_declaration.identifier.clone(), MemoTable::default());
+        } else {
+            self.memo_tables.remove(&function_declaration.identifier);
+        }
         self.function_declarations.insert(function_declaration.identifier.clone(), function_declaration);
         Ok(())
     }
 
     fn interpret_value_declaration(&mut self, value_declaration: ValueDeclaration) -> ExecutionResult {
         // NOTE: Declared values MUST evaluate to a number/specific value at runtime
+        // a_0 = 0 is a base case of the sequence a
+        if let Identifier::SubScriptIdent(subscript) = &value_declaration.identifier {
+            if let Identifier::NumberIdent(index) = &subscript.secnd_ident {
+                let number = self.evaluate_value(value_declaration.value)?;
+                let sequence = self.sequences.entry(subscript.first_ident.clone()).or_default();
+                sequence.base_cases.insert(index.clone(), number);
+                sequence.terms = Default::default();
+                return Ok(());
+            }
+        }
+        // Declarations shadow the prelude, unless that has been ruled out with %jimtex: strict
+        if self.strict && self.constant(&value_declaration.identifier).is_some() {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::ShadowedConstant(value_declaration.identifier)));
+        }
         self.variables.insert(value_declaration.identifier.clone(), self.evaluate_value(value_declaration.value)?);
         Ok(())
     }
 
-    fn interpret_expression(&self, expression: Expression) -> ExecutionResultPrint {
-        let num = self.evaluate_expression(expression)?;
-        Ok(format!("{num}"))
-    }
-
     fn evaluate_value(&self, value: Value) -> ExecutionResultNumber {
         match value {
             Value::Number(num)       => Ok(num),
@@ -180,7 +407,15 @@ impl ProgramInterpreter {
     fn evaluate_expression(&self, expression: Expression) -> ExecutionResultNumber {
         match expression {
             Expression::Value(value)                => self.evaluate_value(*value),
+            Expression::FunctionCall(function_call) if !self.function_definitions.contains_key(&function_call.function) => self.interpret_derived_call(function_call),
             Expression::FunctionCall(function_call) => self.interpret_function_call(function_call),
+            Expression::BuiltinCall(call)           => self.eval_builtin(call),
+            Expression::SequenceTerm(term)          => self.evaluate_sequence_term(&term.sequence, self.evaluate_value(term.index)?),
+            Expression::Limit(limit)                => self.eval_limit(limit),
+            Expression::Matrix(rows)                => self.eval_matrix(rows),
+            Expression::Tuple(values)               => Ok(Number::Tuple(values.into_iter().map(|value| self.evaluate_value(value)).collect::<Result<_, _>>()?)),
+            Expression::Structure(literal)          => self.eval_structure(literal),
+            Expression::Invalid(error)              => Err(RuntimeError::new(self.line, RuntimeErrorTypes::ParseError(error))),
             Expression::UnaryOperation(unop)        => self.eval_unop(unop),
             Expression::BinaryOperation(binop)      => self.eval_binop(binop),
             Expression::Conditional(conditional)    => {
@@ -199,132 +434,95 @@ impl ProgramInterpreter {
     }
 
     fn eval_binop(&self, binop: BinaryOperation) -> ExecutionResultNumber {
-        match binop.binop {
+        if binop.binop == BinOps::Power && binop.value_2 == Value::Identifier(Identifier::TextIdent("T".to_owned())) {
+            return self.eval_transpose(binop);
+        }
+        if let Some(power) = Self::modular_power(&binop) {
+            return self.eval_modular_power(power.clone(), binop.value_2);
+        }
+        let num_1 = self.evaluate_value(binop.value_1)?;
+        let num_2 = self.evaluate_value(binop.value_2)?;
+        self.arithmetic(num_1, binop.binop, num_2)
+    }
+
+    fn arithmetic(&self, num_1: Number, binop: BinOps, num_2: Number) -> ExecutionResultNumber {
+        if let (Number::Matrix(_), _) | (_, Number::Matrix(_)) = (&num_1, &num_2) {
+            return self.matrix_arithmetic(num_1, binop, num_2);
+        }
+        if let (Number::Set(_), _) | (_, Number::Set(_)) = (&num_1, &num_2) {
+            return self.group_arithmetic(num_1, binop, num_2);
+        }
+        if let Some(result) = self.modular_arithmetic(&num_1, &binop, &num_2) {
+            return result;
+        }
+        if let Some(result) = self.symbolic_arithmetic(&num_1, &binop, &num_2) {
+            return result;
+        }
+        if let (Number::Polynomial(_), _) | (_, Number::Polynomial(_)) = (&num_1, &num_2) {
+            return self.polynomial_arithmetic(num_1, binop, num_2);
+        }
+        if let (Number::Infinity(_), _) | (_, Number::Infinity(_)) = (&num_1, &num_2) {
+            return self.infinite_arithmetic(&num_1, &binop, &num_2);
+        }
+        if let Some(number) = self.exact_arithmetic(&num_1, &binop, &num_2) {
+            return Ok(number);
+        }
+        // 7/2 stays a fraction through the field operations
+        if let (Number::Rational(_), Number::Integer(_) | Number::Rational(_)) | (Number::Integer(_), Number::Rational(_)) = (&num_1, &num_2) {
+            if matches!(binop, BinOps::Addition | BinOps::Subtraction | BinOps::Multiply | BinOps::Divide) {
+                return self.field_arithmetic(num_1, binop, num_2);
+            }
+        }
+        let (num_1, num_2) = (self.collapse(num_1), self.collapse(num_2));
+        if let (Number::Complex(_), _) | (_, Number::Complex(_)) = (&num_1, &num_2) {
+            return self.complex_arithmetic(&num_1, &binop, &num_2);
+        }
+        match binop {
+            // Integers stay integers, or fractions when they don't divide, anything else is worked
+            // out as a decimal
             BinOps::Multiply => {
-                let num_1 = self.evaluate_value(binop.value_1)?;
-                let num_2 = self.evaluate_value(binop.value_2)?;
-                match num_1 {
-                    Number::Integer(num_1) => {
-                        match num_2 {
-                            Number::Integer(num_2) => {
-                                Ok(Number::Integer(num_1 * num_2))
-                            },
-                            Number::Real(num_2) => {
-                                let num_1 = num_1.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 * num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    Number::Real(num_1) => {
-                        match num_2 {
-                            Number::Real(num_2) => {
-                                Ok(Number::Real(num_1 * num_2))
-                            },
-                            Number::Integer(num_2) => {
-                                let num_2 = num_2.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 * num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    _ => todo!(),
+                match (num_1, num_2) {
+                    (Number::Integer(num_1), Number::Integer(num_2)) => Ok(Number::Integer(num_1 * num_2)),
+                    (num_1, num_2) => Ok(Number::Real(self.to_decimal(&num_1)? * self.to_decimal(&num_2)?)),
                 }
             },
             BinOps::Divide => {
-                let num_1 = self.evaluate_value(binop.value_1)?;
-                let num_2 = self.evaluate_value(binop.value_2)?;
-                match num_1 {
-                    Number::Integer(num_1) => {
-                        match num_2 {
-                            Number::Integer(num_2) => {
-                                Ok(Number::Integer(num_1 / num_2))
-                            },
-                            Number::Real(num_2) => {
-                                let num_1 = num_1.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 / num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    Number::Real(num_1) => {
-                        match num_2 {
-                            Number::Real(num_2) => {
-                                Ok(Number::Real(num_1 / num_2))
-                            },
-                            Number::Integer(num_2) => {
-                                let num_2 = num_2.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 / num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    _ => todo!(),
+                match (num_1, num_2) {
+                    (_, num_2) if self.to_decimal(&num_2)?.is_zero() => Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined)),
+                    (Number::Integer(num_1), Number::Integer(num_2)) => self.field_arithmetic(Number::Integer(num_1), BinOps::Divide, Number::Integer(num_2)),
+                    (num_1, num_2) => Ok(Number::Real(self.to_decimal(&num_1)?.div(&self.to_decimal(&num_2)?, &self.context))),
                 }
             },
             BinOps::Addition => {
-                let num_1 = self.evaluate_value(binop.value_1)?;
-                let num_2 = self.evaluate_value(binop.value_2)?;
-                match num_1 {
-                    Number::Integer(num_1) => {
-                        match num_2 {
-                            Number::Integer(num_2) => {
-                                Ok(Number::Integer(num_1 + num_2))
-                            },
-                            Number::Real(num_2) => {
-                                let num_1 = num_1.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 + num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    Number::Real(num_1) => {
-                        match num_2 {
-                            Number::Real(num_2) => {
-                                Ok(Number::Real(num_1 + num_2))
-                            },
-                            Number::Integer(num_2) => {
-                                let num_2 = num_2.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 + num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    _ => todo!(),
+                match (num_1, num_2) {
+                    (Number::Integer(num_1), Number::Integer(num_2)) => Ok(Number::Integer(num_1 + num_2)),
+                    (num_1, num_2) => Ok(Number::Real(self.to_decimal(&num_1)? + self.to_decimal(&num_2)?)),
                 }
             },
             BinOps::Subtraction => {
-                let num_1 = self.evaluate_value(binop.value_1)?;
-                let num_2 = self.evaluate_value(binop.value_2)?;
-                match num_1 {
-                    Number::Integer(num_1) => {
-                        match num_2 {
-                            Number::Integer(num_2) => {
-                                Ok(Number::Integer(num_1 - num_2))
-                            },
-                            Number::Real(num_2) => {
-                                let num_1 = num_1.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 - num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    Number::Real(num_1) => {
-                        match num_2 {
-                            Number::Real(num_2) => {
-                                Ok(Number::Real(num_1 - num_2))
-                            },
-                            Number::Integer(num_2) => {
-                                let num_2 = num_2.to_string().parse::<f64>().map_err(|_| RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?; 
-                                Ok(Number::Real(num_1 - num_2))
-                            },
-                            _ => todo!()
-                        }
-                    },
-                    _ => todo!(),
+                match (num_1, num_2) {
+                    (Number::Integer(num_1), Number::Integer(num_2)) => Ok(Number::Integer(num_1 - num_2)),
+                    (num_1, num_2) => Ok(Number::Real(self.to_decimal(&num_1)? - self.to_decimal(&num_2)?)),
                 }
             },
-            _ => todo!(),
+            BinOps::Binomial => {
+                let (Number::Integer(n), Number::Integer(k)) = (num_1, num_2) else {
+                    return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError));
+                };
+                // n (n-1) ... (n-k+1) / k!, which also works for negative n
+                if k < BigInt::from(0u8) {
+                    return Ok(Number::Integer(BigInt::from(0u8)));
+                }
+                let mut binomial = BigInt::from(1u8);
+                for i in num::range(BigInt::from(0u8), k) {
+                    binomial = binomial * (&n - &i) / (i + 1u8);
+                }
+                Ok(Number::Integer(binomial))
+            },
+            BinOps::Modulo => self.eval_modulo(num_1, num_2),
+            BinOps::Power  => self.eval_power(num_1, num_2),
+            // \cup and the rest of the set operations don't apply to numbers
+            _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
         }
     }
 
@@ -336,8 +534,14 @@ impl ProgramInterpreter {
                         match num {
                             Number::Real(real)   => Ok(Number::Real(-real)),
                             Number::Integer(int) => Ok(Number::Integer(-int)),
+                            Number::PiMultiple(coefficient) => Ok(Number::PiMultiple(-coefficient)),
+                            Number::Infinity(sign) => Ok(Number::Infinity(-sign)),
+                            Number::Symbolic(symbolic) => Ok(symbolic::neg(*symbolic).into_number()),
+                            Number::Polynomial(polynomial) => Ok(Number::Polynomial(Box::new(-&*polynomial))),
+                            Number::Matrix(matrix) => self.arithmetic(Number::Integer(BigInt::from(-1)), BinOps::Multiply, Number::Matrix(matrix)),
                             Number::Complex(_)   => todo!(),
-                            Number::Rational(_)  => todo!(),
+                            Number::Rational(rat) => Ok(Number::Rational(-rat)),
+                            Number::Tuple(_) | Number::Set(_) => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
                         }
                     },
                     Value::Identifier(ident) => self.get_ident_val(ident),
@@ -345,13 +549,1045 @@ impl ProgramInterpreter {
                 }
             },
             UnOps::BoolNot => todo!(),
+            UnOps::Factorial => {
+                match self.evaluate_value(unop.value)? {
+                    Number::Integer(n) if n >= BigInt::from(0u8) => Ok(Number::Integer(num::range_inclusive(BigInt::from(1u8), n).product())),
+                    Number::Symbolic(symbolic) => Ok(Number::Symbolic(Box::new(Symbolic::Operation(UnOps::Factorial, symbolic)))),
+                    number => Err(RuntimeError::new(self.line, RuntimeErrorTypes::InvalidFactorial(number))),
+                }
+            },
+            UnOps::AbsoluteValue => {
+                match self.evaluate_value(unop.value)? {
+                    Number::Integer(int)  => Ok(Number::Integer(int.abs())),
+                    Number::Real(real)    => Ok(Number::Real(real.abs())),
+                    Number::Rational(rat) => Ok(Number::Rational(rat.abs())),
+                    Number::Complex(cplx) => self.real_from_f64(cplx.real.hypot(cplx.imag)),
+                    Number::PiMultiple(coefficient) => Ok(Number::PiMultiple(coefficient.abs())),
+                    Number::Infinity(_)   => Ok(Number::Infinity(Sign::Plus)),
+                    Number::Symbolic(symbolic) => Ok(Number::Symbolic(Box::new(Symbolic::Operation(UnOps::AbsoluteValue, symbolic)))),
+                    Number::Matrix(_) | Number::Polynomial(_) | Number::Tuple(_) => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+                    // The order of a group
+                    Number::Set(set) => Ok(Number::Integer(BigInt::from(set.members.len()))),
+                }
+            },
+            UnOps::Floor | UnOps::Ceiling => {
+                let number = self.collapse(self.evaluate_value(unop.value)?);
+                if let Number::Symbolic(symbolic) = number {
+                    return Ok(Number::Symbolic(Box::new(Symbolic::Operation(unop.unop, symbolic))));
+                }
+                let rounded = match (&unop.unop, &number) {
+                    (_, Number::Integer(int)) => Some(int.clone()),
+                    (UnOps::Floor, Number::Real(real))    => Some(real.floor()),
+                    (_,            Number::Real(real))    => Some(real.ceil()),
+                    (UnOps::Floor, Number::Rational(rat)) => Some(rat.floor().to_integer()),
+                    (_,            Number::Rational(rat)) => Some(rat.ceil().to_integer()),
+                    (_, Number::Complex(_) | Number::PiMultiple(_) | Number::Infinity(_) | Number::Symbolic(_) | Number::Matrix(_) | Number::Polynomial(_) | Number::Tuple(_) | Number::Set(_)) => None,
+                };
+                rounded.map(Number::Integer).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))
+            },
+        }
+    }
+
+    // With a variable on either side, or fractions in symbolic mode, the result is simplified instead of evaluated
+    fn symbolic_arithmetic(&self, num_1: &Number, binop: &BinOps, num_2: &Number) -> Option<ExecutionResultNumber> {
+        let exact = |number: &Number| matches!(number, Number::Integer(_) | Number::Rational(_));
+        let variable = matches!(num_1, Number::Symbolic(_)) || matches!(num_2, Number::Symbolic(_));
+        if !(variable || self.symbolic && exact(num_1) && exact(num_2)) {
+            return None;
+        }
+        let (Some(a), Some(b)) = (Symbolic::from_number(num_1), Symbolic::from_number(num_2)) else {
+            return Some(Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)));
+        };
+        let zero = symbolic::integer(0);
+        let negative = matches!(&b, Symbolic::Constant(exponent) if exponent.is_negative());
+        let result = match binop {
+            BinOps::Addition    => symbolic::add(a, b),
+            BinOps::Subtraction => symbolic::sub(a, b),
+            BinOps::Multiply    => symbolic::mul(a, b),
+            BinOps::Divide if b == zero => return Some(Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined))),
+            BinOps::Power if a == zero && negative => return Some(Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined))),
+            BinOps::Divide      => symbolic::div(a, b),
+            BinOps::Power       => symbolic::pow(a, b),
+            // \bmod and \binom of integers work as usual
+            _ if !variable => return None,
+            _ => return Some(Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))),
+        };
+        Some(Ok(result.into_number()))
+    }
+
+    // Keeps π exact, a rational multiple of π only stays one through +, -, * and / by rationals
+    fn exact_arithmetic(&self, num_1: &Number, binop: &BinOps, num_2: &Number) -> Option<Number> {
+        let rational = |number: &Number| match number {
+            Number::Integer(int)  => Some(BigRational::from_integer(int.clone())),
+            Number::Rational(rat) => Some(rat.clone()),
+            _ => None,
+        };
+        let coefficient = match (num_1, binop, num_2) {
+            (Number::PiMultiple(a), BinOps::Addition,    Number::PiMultiple(b)) => a + b,
+            (Number::PiMultiple(a), BinOps::Subtraction, Number::PiMultiple(b)) => a - b,
+            (Number::PiMultiple(a), BinOps::Multiply, k) | (k, BinOps::Multiply, Number::PiMultiple(a)) => a * rational(k)?,
+            (Number::PiMultiple(a), BinOps::Divide, Number::PiMultiple(b)) if !b.is_zero() => {
+                let ratio = a / b;
+                return Some(if ratio.is_integer() { Number::Integer(ratio.to_integer()) } else { Number::Rational(ratio) });
+            },
+            (Number::PiMultiple(a), BinOps::Divide, k) => a / rational(k).filter(|k| !k.is_zero())?,
+            _ => return None,
+        };
+        Some(if coefficient.is_zero() { Number::Integer(BigInt::zero()) } else { Number::PiMultiple(coefficient) })
+    }
+
+    // Where exactness can't be kept, π becomes a real
+    fn collapse(&self, number: Number) -> Number {
+        match number {
+            Number::PiMultiple(coefficient) => {
+                let numerator = Real::pi(&self.context) * Real::from(coefficient.numer().clone());
+                Number::Real(numerator.div(&Real::from(coefficient.denom().clone()), &self.context))
+            },
+            number => number,
+        }
+    }
+
+    // Only what has a limit, ∞ - ∞ and 0 * ∞ are undefined
+    fn infinite_arithmetic(&self, num_1: &Number, binop: &BinOps, num_2: &Number) -> ExecutionResultNumber {
+        let undefined = RuntimeError::new(self.line, RuntimeErrorTypes::Undefined);
+        let sign = |number: &Number| match number {
+            Number::Infinity(sign) => Ok(*sign),
+            number => {
+                let real = self.to_decimal(number)?;
+                Ok(if real.is_zero() { Sign::NoSign } else if real.is_negative() { Sign::Minus } else { Sign::Plus })
+            },
+        };
+        let (sign_1, sign_2) = (sign(num_1)?, sign(num_2)?);
+        let infinite = (matches!(num_1, Number::Infinity(_)), matches!(num_2, Number::Infinity(_)));
+        match (binop, infinite) {
+            (BinOps::Addition, (true, true)) if sign_1 != sign_2 => Err(undefined),
+            (BinOps::Subtraction, (true, true)) if sign_1 == sign_2 => Err(undefined),
+            (BinOps::Addition | BinOps::Subtraction, (true, _)) => Ok(Number::Infinity(sign_1)),
+            (BinOps::Addition, (false, true)) => Ok(Number::Infinity(sign_2)),
+            (BinOps::Subtraction, (false, true)) => Ok(Number::Infinity(-sign_2)),
+            (BinOps::Multiply, _) if sign_1 == Sign::NoSign || sign_2 == Sign::NoSign => Err(undefined),
+            (BinOps::Multiply, _) => Ok(Number::Infinity(sign_1 * sign_2)),
+            (BinOps::Divide, (true, true)) => Err(undefined),
+            (BinOps::Divide, (true, false)) if sign_2 == Sign::NoSign => Err(undefined),
+            (BinOps::Divide, (true, false)) => Ok(Number::Infinity(sign_1 * sign_2)),
+            (BinOps::Divide, (false, true)) => Ok(Number::Real(Real::from(BigInt::zero()))),
+            _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        }
+    }
+
+    fn complex_arithmetic(&self, num_1: &Number, binop: &BinOps, num_2: &Number) -> ExecutionResultNumber {
+        let complex = |number: &Number| match number {
+            Number::Complex(cplx) => Ok((cplx.real, cplx.imag)),
+            number => Ok((self.to_f64(number)?, 0.0)),
+        };
+        let ((a, b), (c, d)) = (complex(num_1)?, complex(num_2)?);
+        let (real, imag) = match binop {
+            BinOps::Addition    => (a + c, b + d),
+            BinOps::Subtraction => (a - c, b - d),
+            BinOps::Multiply    => (a * c - b * d, a * d + b * c),
+            BinOps::Divide      => {
+                let denominator = c * c + d * d;
+                ((a * c + b * d) / denominator, (b * c - a * d) / denominator)
+            },
+            // Real powers, through the polar form
+            BinOps::Power if d == 0.0 => {
+                let (modulus, argument) = (a.hypot(b).powf(c), b.atan2(a) * c);
+                (modulus * argument.cos(), modulus * argument.sin())
+            },
+            _ => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        };
+        if !(real.is_finite() && imag.is_finite()) {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined));
+        }
+        Ok(Number::Complex(Complex { real, imag }))
+    }
+
+    // Whole powers are exact, anything else is worked out in double precision
+    fn eval_power(&self, base: Number, exponent: Number) -> ExecutionResultNumber {
+        match (base, exponent) {
+            (Number::Integer(base), Number::Integer(exponent)) if !exponent.is_negative() => {
+                let exponent = exponent.to_usize().ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined))?;
+                Ok(Number::Integer(num::pow(base, exponent)))
+            },
+            (base, Number::Integer(exponent)) => {
+                let base = self.to_decimal(&base)?;
+                let mut power = Real::from(BigInt::from(1u8));
+                for _ in num::range(BigInt::zero(), exponent.abs()) {
+                    power = power * base.clone();
+                }
+                match (exponent.is_negative(), power.is_zero()) {
+                    (false, _)    => Ok(Number::Real(power)),
+                    (true, true)  => Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined)),
+                    (true, false) => Ok(Number::Real(Real::from(BigInt::from(1u8)).div(&power, &self.context))),
+                }
+            },
+            (base, exponent) => self.real_from_f64(self.to_f64(&base)?.powf(self.to_f64(&exponent)?)),
+        }
+    }
+
+    fn eval_modulo(&self, num_1: Number, num_2: Number) -> ExecutionResultNumber {
+        match (num_1, num_2) {
+            (_, num_2) if self.to_decimal(&num_2)?.is_zero() => Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined)),
+            (Number::Integer(num_1), Number::Integer(num_2)) => Ok(Number::Integer(num_1.mod_floor(&num_2.abs()))),
+            // a/b \bmod n is a b^{-1}
+            (Number::Rational(num_1), Number::Integer(num_2)) => modular::residue(&num_1, &num_2.abs())
+                .map(Number::Integer)
+                .ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotInvertible(num_1.denom().clone(), num_2.abs()))),
+            (num_1, num_2) => Ok(Number::Real(self.to_decimal(&num_1)?.rem_euclid(&self.to_decimal(&num_2)?))),
+        }
+    }
+
+    fn eval_builtin(&self, call: BuiltinCall) -> ExecutionResultNumber {
+        match call.function {
+            Builtin::Derivative => return self.eval_derivative(call.args),
+            Builtin::Integral   => return self.eval_integral(call.args),
+            Builtin::Congruence => return self.eval_congruence(call.args),
+            _ => (),
+        }
+        let args = call.args.into_iter().map(|value| self.evaluate_value(value)).collect::<Result<Vec<_>, _>>()?;
+        let wrong_arguments = RuntimeError::new(self.line, RuntimeErrorTypes::WrongNumberOfArguments(call.function.clone(), args.len()));
+        // \sin x of a variable x stays a call, a determinant is worked out symbolically by arithmetic
+        if call.function != Builtin::Det && args.iter().any(|number| matches!(number, Number::Symbolic(_))) {
+            let args = args.iter().map(Symbolic::from_number).collect::<Option<Vec<_>>>().ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))?;
+            return Ok(symbolic::call(call.function, args).into_number());
+        }
+        match (&call.function, &args[..]) {
+            (Builtin::Gcd | Builtin::Factor | Builtin::Roots, [Number::Polynomial(_), ..]) => self.eval_polynomial_builtin(&call.function, &args),
+            (Builtin::Order | Builtin::Generated, [elements @ .., Number::Set(group)]) => self.eval_group_builtin(&call.function, elements, group),
+            // Numbers are as simple as they get
+            (Builtin::Expand | Builtin::Factor, [x]) => Ok(x.clone()),
+            (Builtin::Gcd | Builtin::Lcm, [first, rest @ ..]) if !rest.is_empty() => {
+                let mut result = self.to_integer(first)?;
+                for number in rest {
+                    let number = self.to_integer(number)?;
+                    result = match call.function {
+                        Builtin::Gcd => result.gcd(&number),
+                        _            => result.lcm(&number),
+                    };
+                }
+                Ok(Number::Integer(result))
+            },
+            // Whichever argument it is, 2 stays an integer in \max(2, 1.5)
+            (Builtin::Min | Builtin::Max, [first, rest @ ..]) => {
+                let mut result = first;
+                for number in rest {
+                    let (real, best) = (self.to_decimal(number)?, self.to_decimal(result)?);
+                    if (call.function == Builtin::Min && real < best) || (call.function == Builtin::Max && real > best) {
+                        result = number;
+                    }
+                }
+                Ok(result.clone())
+            },
+            (Builtin::Det, [Number::Matrix(matrix)]) if matrix.is_square() => self.determinant(matrix.entries.clone(), matrix.rows),
+            (Builtin::Det | Builtin::Rank | Builtin::Rref, [Number::Matrix(matrix)]) => {
+                let (reduced, pivots) = self.row_reduce(matrix)?;
+                match call.function {
+                    Builtin::Rank => Ok(Number::Integer(BigInt::from(pivots.len()))),
+                    Builtin::Rref => Ok(Number::Matrix(Box::new(reduced))),
+                    _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::NotSquare)),
+                }
+            },
+            (Builtin::Det, matrix) => {
+                let size = (matrix.len() as f64).sqrt() as usize;
+                if matrix.is_empty() || size * size != matrix.len() {
+                    return Err(wrong_arguments);
+                }
+                self.determinant(matrix.to_vec(), size)
+            },
+            // No power of 1 or of a base that isn't positive gives every x
+            (Builtin::Log, [x, base]) => match self.to_f64(base)? {
+                base if base <= 0.0 || base == 1.0 => Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined)),
+                base => self.real_from_f64(self.to_f64(x)?.log(base)),
+            },
+            (function, [x]) => {
+                // Computed in double precision
+                let x = self.to_f64(x)?;
+                self.real_from_f64(match function {
+                    Builtin::Sin    => x.sin(),
+                    Builtin::Cos    => x.cos(),
+                    Builtin::Tan    => x.tan(),
+                    Builtin::Sec    => x.cos().recip(),
+                    Builtin::Csc    => x.sin().recip(),
+                    Builtin::Cot    => x.tan().recip(),
+                    Builtin::Arcsin => x.asin(),
+                    Builtin::Arccos => x.acos(),
+                    Builtin::Arctan => x.atan(),
+                    Builtin::Sinh   => x.sinh(),
+                    Builtin::Cosh   => x.cosh(),
+                    Builtin::Tanh   => x.tanh(),
+                    Builtin::Coth   => x.tanh().recip(),
+                    Builtin::Ln     => x.ln(),
+                    Builtin::Log    => x.log10(),
+                    Builtin::Exp    => x.exp(),
+                    _ => return Err(wrong_arguments),
+                })
+            },
+            _ => Err(wrong_arguments),
+        }
+    }
+
+    // Entries are evaluated like any other value, but a matrix can't be an entry
+    fn eval_matrix(&self, rows: Vec<Vec<Value>>) -> ExecutionResultNumber {
+        let rows = rows.into_iter()
+            .map(|row| row.into_iter().map(|value| self.evaluate_value(value)).collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if rows.iter().flatten().any(|entry| matches!(entry, Number::Matrix(_))) {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError));
+        }
+        Matrix::from_rows(rows).map(|matrix| Number::Matrix(Box::new(matrix))).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::DimensionMismatch))
+    }
+
+    // A^T, unless A isn't a matrix and T is just a variable
+    fn eval_transpose(&self, binop: BinaryOperation) -> ExecutionResultNumber {
+        match self.evaluate_value(binop.value_1)? {
+            Number::Matrix(matrix) => Ok(Number::Matrix(Box::new(matrix.transpose()))),
+            number => self.arithmetic(number, BinOps::Power, self.evaluate_value(binop.value_2)?),
+        }
+    }
+
+    fn matrix_arithmetic(&self, num_1: Number, binop: BinOps, num_2: Number) -> ExecutionResultNumber {
+        let mismatch = RuntimeError::new(self.line, RuntimeErrorTypes::DimensionMismatch);
+        let entrywise = |matrix: &Matrix, f: &dyn Fn(Number) -> ExecutionResultNumber| -> ExecutionResultNumber {
+            let entries = matrix.entries.iter().map(|entry| f(entry.clone())).collect::<Result<_, _>>()?;
+            Ok(Number::Matrix(Box::new(Matrix { entries, ..*matrix })))
+        };
+        match (num_1, binop, num_2) {
+            (Number::Matrix(a), binop @ (BinOps::Addition | BinOps::Subtraction), Number::Matrix(b)) => {
+                if (a.rows, a.columns) != (b.rows, b.columns) {
+                    return Err(mismatch);
+                }
+                let entries = a.entries.iter().zip(&b.entries)
+                    .map(|(x, y)| self.field_arithmetic(x.clone(), binop.clone(), y.clone()))
+                    .collect::<Result<_, _>>()?;
+                Ok(Number::Matrix(Box::new(Matrix { entries, ..*a })))
+            },
+            // u \cdot v of two vectors the same length is their dot product
+            (Number::Matrix(u), BinOps::Multiply, Number::Matrix(v)) if u.columns == 1 && v.columns == 1 && u.rows == v.rows && u.rows > 1 => {
+                u.entries.iter().zip(&v.entries).try_fold(Number::Integer(BigInt::zero()), |sum, (x, y)| {
+                    self.field_arithmetic(sum, BinOps::Addition, self.field_arithmetic(x.clone(), BinOps::Multiply, y.clone())?)
+                })
+            },
+            (Number::Matrix(a), BinOps::Multiply, Number::Matrix(b)) => self.matrix_product(&a, &b).map(|product| Number::Matrix(Box::new(product))),
+            (Number::Matrix(a), BinOps::Multiply, k) | (k, BinOps::Multiply, Number::Matrix(a)) => {
+                entrywise(&a, &|entry| self.field_arithmetic(k.clone(), BinOps::Multiply, entry))
+            },
+            (Number::Matrix(a), BinOps::Divide, k) if !matches!(k, Number::Matrix(_)) => {
+                entrywise(&a, &|entry| self.field_arithmetic(entry, BinOps::Divide, k.clone()))
+            },
+            (Number::Matrix(a), BinOps::Power, Number::Integer(n)) => self.matrix_power(&a, n).map(|power| Number::Matrix(Box::new(power))),
+            _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        }
+    }
+
+    fn matrix_product(&self, a: &Matrix, b: &Matrix) -> Result<Matrix, RuntimeError> {
+        if a.columns != b.rows {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::DimensionMismatch));
+        }
+        let mut entries = Vec::with_capacity(a.rows * b.columns);
+        for row in 0..a.rows {
+            for column in 0..b.columns {
+                let mut entry = Number::Integer(BigInt::zero());
+                for k in 0..a.columns {
+                    let product = self.field_arithmetic(a.get(row, k).clone(), BinOps::Multiply, b.get(k, column).clone())?;
+                    entry = self.field_arithmetic(entry, BinOps::Addition, product)?;
+                }
+                entries.push(entry);
+            }
+        }
+        Ok(Matrix { rows: a.rows, columns: b.columns, entries })
+    }
+
+    // By squaring, A^{-n} is the inverse to the n and A^0 the identity
+    fn matrix_power(&self, matrix: &Matrix, n: BigInt) -> Result<Matrix, RuntimeError> {
+        if !matrix.is_square() {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::NotSquare));
+        }
+        let mut base = if n.is_negative() { self.inverse(matrix)? } else { matrix.clone() };
+        let mut n = n.abs();
+        let mut power = Matrix::identity(matrix.rows);
+        while n.is_positive() {
+            if n.is_odd() {
+                power = self.matrix_product(&power, &base)?;
+            }
+            base = self.matrix_product(&base, &base)?;
+            n >>= 1;
+        }
+        Ok(power)
+    }
+
+    // Gauss-Jordan on A next to the identity, which ends up as the inverse
+    fn inverse(&self, matrix: &Matrix) -> Result<Matrix, RuntimeError> {
+        let size = matrix.rows;
+        let identity = Matrix::identity(size);
+        let entries = (0..size).flat_map(|row| matrix.row(row).iter().chain(identity.row(row))).cloned().collect();
+        let (reduced, pivots) = self.row_reduce(&Matrix { rows: size, columns: 2 * size, entries })?;
+        if pivots.len() < size || pivots[size - 1] >= size {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::SingularMatrix));
+        }
+        let entries = (0..size).flat_map(|row| reduced.row(row)[size..].to_vec()).collect();
+        Ok(Matrix { rows: size, columns: size, entries })
+    }
+
+    // Gauss-Jordan elimination to reduced row echelon form, and the columns the pivots are in
+    fn row_reduce(&self, matrix: &Matrix) -> Result<(Matrix, Vec<usize>), RuntimeError> {
+        let mut rows = (0..matrix.rows).map(|row| matrix.row(row).to_vec()).collect::<Vec<_>>();
+        let mut pivots = vec![];
+        for column in 0..matrix.columns {
+            let top = pivots.len();
+            let Some(pivot) = (top..matrix.rows).find(|row| !self.is_zero(&rows[*row][column])) else {
+                continue;
+            };
+            rows.swap(top, pivot);
+            let leading = rows[top][column].clone();
+            rows[top] = rows[top].iter().map(|entry| self.field_arithmetic(entry.clone(), BinOps::Divide, leading.clone())).collect::<Result<_, _>>()?;
+            for row in (0..matrix.rows).filter(|row| *row != top) {
+                let factor = rows[row][column].clone();
+                if self.is_zero(&factor) {
+                    continue;
+                }
+                rows[row] = rows[row].iter().zip(&rows[top]).map(|(entry, pivot_entry)| {
+                    let product = self.field_arithmetic(factor.clone(), BinOps::Multiply, pivot_entry.clone())?;
+                    self.field_arithmetic(entry.clone(), BinOps::Subtraction, product)
+                }).collect::<Result<_, _>>()?;
+            }
+            pivots.push(column);
+            if pivots.len() == matrix.rows {
+                break;
+            }
+        }
+        Ok((Matrix { entries: rows.concat(), ..matrix.clone() }, pivots))
+    }
+
+    fn is_zero(&self, number: &Number) -> bool {
+        match number {
+            Number::Integer(int)  => int.is_zero(),
+            Number::Rational(rat) => rat.is_zero(),
+            Number::Real(real)    => real.is_zero(),
+            _ => false,
+        }
+    }
+
+    // Integers and fractions stay exact, even when dividing, so eliminating doesn't lose precision
+    fn field_arithmetic(&self, num_1: Number, binop: BinOps, num_2: Number) -> ExecutionResultNumber {
+        let rational = |number: &Number| match number {
+            Number::Integer(int)  => Some(BigRational::from_integer(int.clone())),
+            Number::Rational(rat) => Some(rat.clone()),
+            _ => None,
+        };
+        let (Some(a), Some(b)) = (rational(&num_1), rational(&num_2)) else {
+            return self.arithmetic(num_1, binop, num_2);
+        };
+        let result = match binop {
+            BinOps::Addition    => a + b,
+            BinOps::Subtraction => a - b,
+            BinOps::Multiply    => a * b,
+            BinOps::Divide if b.is_zero() => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined)),
+            BinOps::Divide      => a / b,
+            _ => return self.arithmetic(num_1, binop, num_2),
+        };
+        Ok(if result.is_integer() { Number::Integer(result.to_integer()) } else { Number::Rational(result) })
+    }
+
+    // p(x) = x^3 - 2x + 1 as a value, over the ring of the domain it's declared on
+    fn function_polynomial(&self, function: &Identifier) -> ExecutionResultNumber {
+        let not_polynomial = RuntimeError::new(self.line, RuntimeErrorTypes::NotPolynomial(function.clone()));
+        let definition = &self.function_definitions[function];
+        let [x] = &definition.arguments[..] else {
+            return Err(not_polynomial);
+        };
+        let ring = match self.function_declarations.get(function) {
+            Some(declaration) => self.ring(&declaration.domain)?,
+            None => Ring::Rationals,
+        };
+        let mut scope = self.clone();
+        scope.symbolic = true;
+        scope.variables.remove(x);
+        let number = scope.evaluate_expression(definition.expression.clone())?;
+        Symbolic::from_number(&number)
+            .and_then(|symbolic| Polynomial::from_symbolic(&symbolic, x, ring))
+            .map(|polynomial| Number::Polynomial(Box::new(polynomial)))
+            .ok_or(not_polynomial)
+    }
+
+    // Every product is worked out once, then the axioms are checked on the tables
+    fn eval_structure(&self, literal: StructureLiteral) -> ExecutionResultNumber {
+        let mut scope = self.clone();
+        let elements = match literal.carrier {
+            Carrier::Elements(values) => {
+                let mut elements: Vec<Number> = vec![];
+                for value in values {
+                    let element = self.evaluate_value(value)?;
+                    if !elements.contains(&element) {
+                        elements.push(element);
+                    }
+                }
+                elements
+            },
+            // + and \cdot wrap
+            Carrier::Set(set) => match self.ring(&set) {
+                Ok(Ring::Modular(n)) => {
+                    let order = n.to_usize().filter(|order| *order <= algebra::ORDER_LIMIT)
+                        .ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::TooManyElements(n.to_usize().unwrap_or(usize::MAX))))?;
+                    scope.modulus = Some(n);
+                    (0..order).map(|i| Number::Integer(BigInt::from(i))).collect()
+                },
+                _ => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::InfiniteCarrier)),
+            },
+        };
+        if elements.len() > algebra::ORDER_LIMIT {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TooManyElements(elements.len())));
+        }
+        if !(1..=2).contains(&literal.operations.len()) {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError));
         }
+        let products = literal.operations.into_iter().map(|operation| scope.products(&elements, operation)).collect::<Result<Vec<_>, _>>()?;
+        let structure = Structure::new(elements, products).map_err(|failure| RuntimeError::new(self.line, RuntimeErrorTypes::AxiomFails(failure)))?;
+        Ok(Number::Set(Box::new(Subset::whole(structure))))
+    }
+
+    // a * b for every pair of elements, from an operator, a function of two arguments or a Cayley table
+    fn products(&self, elements: &[Number], operation: Operation) -> Result<Vec<Vec<Number>>, RuntimeError> {
+        let pairs = |product: &dyn Fn(&Number, &Number) -> ExecutionResultNumber| {
+            elements.iter().map(|a| elements.iter().map(|b| product(a, b)).collect::<Result<Vec<_>, _>>()).collect::<Result<Vec<_>, _>>()
+        };
+        match operation {
+            Operation::BinOp(binop) => pairs(&|a, b| self.arithmetic(a.clone(), binop.clone(), b.clone())),
+            Operation::Value(Value::Identifier(function)) if self.function_definitions.contains_key(&function) => pairs(&|a, b| {
+                self.interpret_function_call(FunctionCall { function: function.clone(), args: vec![Value::Number(a.clone()), Value::Number(b.clone())] })
+            }),
+            Operation::Value(table) => match self.evaluate_value(table)? {
+                Number::Matrix(matrix) if matrix.rows == elements.len() && matrix.columns == elements.len() => {
+                    Ok((0..matrix.rows).map(|row| matrix.row(row).to_vec()).collect())
+                },
+                Number::Matrix(_) => Err(RuntimeError::new(self.line, RuntimeErrorTypes::DimensionMismatch)),
+                _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+            },
+        }
+    }
+
+    // G \times H and G \extprod H, H \intprod K of subgroups of one group, and cosets a + H, aH and Ha
+    fn group_arithmetic(&self, num_1: Number, binop: BinOps, num_2: Number) -> ExecutionResultNumber {
+        let set = |set: Subset| Ok(Number::Set(Box::new(set)));
+        match (num_1, binop, num_2) {
+            (Number::Set(g), BinOps::Multiply | BinOps::ExternalDirectProduct, Number::Set(h)) => {
+                if !g.is_subgroup() || !h.is_subgroup() {
+                    return Err(RuntimeError::new(self.line, RuntimeErrorTypes::NotASubgroup));
+                }
+                let order = g.members.len() * h.members.len();
+                if order > algebra::ORDER_LIMIT {
+                    return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TooManyElements(order)));
+                }
+                set(Subset::whole(g.to_structure().product(&h.to_structure())))
+            },
+            (Number::Set(h), BinOps::InternalDirectProduct, Number::Set(k)) => {
+                h.internal_product(&k).map(|product| Number::Set(Box::new(product))).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotInternalProduct))
+            },
+            (a, BinOps::Addition | BinOps::Multiply, Number::Set(h)) => set(h.coset(self.element(&h, a)?, false)),
+            (Number::Set(h), BinOps::Addition | BinOps::Multiply, a) => {
+                let a = self.element(&h, a)?;
+                set(h.coset(a, true))
+            },
+            _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        }
+    }
+
+    fn element(&self, group: &Subset, element: Number) -> Result<usize, RuntimeError> {
+        group.structure.index(&element).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotAnElement(element)))
+    }
+
+    // \operatorname{ord}_G(a) and \langle a, b \rangle_G
+    fn eval_group_builtin(&self, function: &Builtin, elements: &[Number], group: &Subset) -> ExecutionResultNumber {
+        let elements = elements.iter().map(|element| self.element(group, element.clone())).collect::<Result<Vec<_>, _>>()?;
+        match (function, &elements[..]) {
+            (Builtin::Order, [a]) => Ok(Number::Integer(BigInt::from(group.structure.order(*a)))),
+            (Builtin::Generated, generators) => Ok(Number::Set(Box::new(Subset::generated(Rc::clone(&group.structure), generators)))),
+            (function, elements) => Err(RuntimeError::new(self.line, RuntimeErrorTypes::WrongNumberOfArguments(function.clone(), elements.len() + 1))),
+        }
+    }
+
+    // Functions declared over \Z/n\Z wrap their arguments, arithmetic and result
+    fn function_modulus(&self, function: &Identifier) -> Result<Option<BigInt>, RuntimeError> {
+        let Some(declaration) = self.function_declarations.get(function) else {
+            return Ok(None);
+        };
+        let modular = [&declaration.domain, &declaration.codomain].into_iter()
+            .find(|set| matches!(set, Token::NumberSet(set) if set.modulus.is_some()));
+        match modular.map(|set| self.ring(set)).transpose()? {
+            Some(Ring::Modular(n)) => Ok(Some(n)),
+            _ => Ok(None),
+        }
+    }
+
+    fn wrap(&self, number: Number) -> Number {
+        match (&self.modulus, number) {
+            (Some(n), Number::Integer(int)) => Number::Integer(int.mod_floor(n)),
+            (Some(n), Number::Rational(rat)) => match modular::residue(&rat, n) {
+                Some(residue) => Number::Integer(residue),
+                None => Number::Rational(rat),
+            },
+            (_, number) => number,
+        }
+    }
+
+    // Division is multiplication by the inverse, a^{-1} is the inverse
+    fn modular_arithmetic(&self, num_1: &Number, binop: &BinOps, num_2: &Number) -> Option<ExecutionResultNumber> {
+        let (Some(n), Number::Integer(a), Number::Integer(b)) = (&self.modulus, num_1, num_2) else {
+            return None;
+        };
+        let not_invertible = |a: &BigInt| Some(Err(RuntimeError::new(self.line, RuntimeErrorTypes::NotInvertible(a.clone(), n.clone()))));
+        let result = match binop {
+            BinOps::Addition    => a + b,
+            BinOps::Subtraction => a - b,
+            BinOps::Multiply    => a * b,
+            BinOps::Divide => match modular::inverse(b, n) {
+                Some(inverse) => a * inverse,
+                None => return not_invertible(b),
+            },
+            BinOps::Power => match modular::power(a, b, n) {
+                Some(power) => power,
+                None => return not_invertible(a),
+            },
+            _ => return None,
+        };
+        Some(Ok(Number::Integer(result.mod_floor(n))))
+    }
+
+    // a^k \bmod n
+    fn modular_power(binop: &BinaryOperation) -> Option<&BinaryOperation> {
+        match (&binop.binop, &binop.value_1) {
+            (BinOps::Modulo, Value::Expression(expression)) => match &**expression {
+                Expression::BinaryOperation(power) if power.binop == BinOps::Power => Some(power),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Without working out a^k, so 2^{1000} \bmod 7 is cheap and 3^{-1} \bmod 7 is the inverse
+    fn eval_modular_power(&self, power: BinaryOperation, modulus: Value) -> ExecutionResultNumber {
+        let base = self.evaluate_value(power.value_1)?;
+        let exponent = self.evaluate_value(power.value_2)?;
+        let n = self.evaluate_value(modulus)?;
+        if let (Number::Integer(a), Number::Integer(k), Number::Integer(n)) = (&base, &exponent, &n) {
+            if !n.is_zero() {
+                return modular::power(a, k, &n.abs()).map(Number::Integer)
+                    .ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotInvertible(a.clone(), n.abs())));
+            }
+        }
+        let power = self.arithmetic(base, BinOps::Power, exponent)?;
+        self.eval_modulo(power, n)
+    }
+
+    // 1 when a \equiv b \pmod{n} and 0 otherwise, both sides are worked out in \Z/n\Z. Without a
+    // modulus it's equality, which is congruence inside a function over \Z/n\Z
+    fn eval_congruence(&self, args: Vec<Value>) -> ExecutionResultNumber {
+        let wrong_arguments = RuntimeError::new(self.line, RuntimeErrorTypes::WrongNumberOfArguments(Builtin::Congruence, args.len()));
+        let mut scope = self.clone();
+        let (a, b) = match <[Value; 2]>::try_from(args) {
+            Ok([a, b]) => (a, b),
+            Err(args) => {
+                let Ok([a, b, modulus]) = <[Value; 3]>::try_from(args) else {
+                    return Err(wrong_arguments);
+                };
+                match self.evaluate_value(modulus)? {
+                    Number::Integer(n) if n.is_positive() => scope.modulus = Some(n),
+                    _ => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+                }
+                (a, b)
+            },
+        };
+        let a = scope.wrap(scope.evaluate_value(a)?);
+        let b = scope.wrap(scope.evaluate_value(b)?);
+        Ok(Number::Integer(BigInt::from((a == b) as u8)))
+    }
+
+    // \Z, \Q, \R and \Z/n\Z, a function without a domain is over \Q
+    fn ring(&self, domain: &Token) -> Result<Ring, RuntimeError> {
+        Ok(match domain {
+            Token::Integers | Token::NatrualNumbers => Ring::Integers,
+            Token::RealNumbers => Ring::Reals,
+            Token::NumberSet(set) => match &set.modulus {
+                Some(modulus) => match self.evaluate_value(modulus.clone())? {
+                    Number::Integer(n) if n > BigInt::from(1u8) => Ring::Modular(n),
+                    _ => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+                },
+                None => return self.ring(&set.set),
+            },
+            Token::ComplexNumbers => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+            _ => Ring::Rationals,
+        })
+    }
+
+    // p / q is the quotient and p \bmod q the remainder, numbers are constant polynomials
+    fn polynomial_arithmetic(&self, num_1: Number, binop: BinOps, num_2: Number) -> ExecutionResultNumber {
+        let type_error = || RuntimeError::new(self.line, RuntimeErrorTypes::TypeError);
+        let polynomial = |result: Polynomial| Ok(Number::Polynomial(Box::new(result)));
+        if let (Number::Polynomial(p), BinOps::Power, Number::Integer(n)) = (&num_1, &binop, &num_2) {
+            return polynomial(p.pow(n.to_u32().ok_or_else(type_error)?));
+        }
+        let constant = |like: &Polynomial, number: &Number| {
+            let constant = match self.collapse(number.clone()) {
+                Number::Integer(int)  => BigRational::from_integer(int),
+                Number::Rational(rat) => rat,
+                Number::Real(real)    => real.to_rational(),
+                _ => return None,
+            };
+            like.constant(constant)
+        };
+        let (p, q) = match (num_1, num_2) {
+            (Number::Polynomial(p), Number::Polynomial(q)) => self.common_ring(*p, *q)?,
+            (Number::Polynomial(p), number) => {
+                let q = constant(&p, &number).ok_or_else(type_error)?;
+                (*p, q)
+            },
+            (number, Number::Polynomial(q)) => (constant(&q, &number).ok_or_else(type_error)?, *q),
+            _ => return Err(type_error()),
+        };
+        match binop {
+            BinOps::Addition    => polynomial(&p + &q),
+            BinOps::Subtraction => polynomial(&p - &q),
+            BinOps::Multiply    => polynomial(&p * &q),
+            BinOps::Divide | BinOps::Modulo => {
+                let (quotient, remainder) = p.div_rem(&q).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotDivisible))?;
+                polynomial(if binop == BinOps::Divide { quotient } else { remainder })
+            },
+            _ => Err(type_error()),
+        }
+    }
+
+    // Both in the same variable, and over whichever ring has the other in it
+    fn common_ring(&self, p: Polynomial, q: Polynomial) -> Result<(Polynomial, Polynomial), RuntimeError> {
+        match p.ring.join(&q.ring) {
+            Some(ring) if p.variable == q.variable => Ok((Polynomial { ring: ring.clone(), ..p }, Polynomial { ring, ..q })),
+            _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        }
+    }
+
+    // \gcd(p, q), \operatorname{factor}(p) over \Q and \operatorname{roots}(p) as a vector
+    fn eval_polynomial_builtin(&self, function: &Builtin, args: &[Number]) -> ExecutionResultNumber {
+        let type_error = RuntimeError::new(self.line, RuntimeErrorTypes::TypeError);
+        match (function, args) {
+            (Builtin::Gcd, [Number::Polynomial(p), Number::Polynomial(q)]) => {
+                let (p, q) = self.common_ring(*p.clone(), *q.clone())?;
+                p.gcd(&q).map(|gcd| Number::Polynomial(Box::new(gcd))).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotDivisible))
+            },
+            (Builtin::Factor, [Number::Polynomial(p)]) => {
+                let (constant, factors) = p.factor().ok_or(type_error)?;
+                let factors = factors.into_iter().map(|(factor, multiplicity)| symbolic::pow(factor.to_symbolic(), symbolic::integer(multiplicity as i64)));
+                Ok(symbolic::product(std::iter::once(Symbolic::Constant(constant)).chain(factors).collect()).into_number())
+            },
+            (Builtin::Roots, [Number::Polynomial(p)]) => {
+                let (exact, numerical) = p.roots().ok_or(type_error)?;
+                let mut roots = exact.into_iter()
+                    .map(|root| (root.to_f64().unwrap_or(f64::NAN), 0.0, Symbolic::Constant(root).into_number()))
+                    .collect::<Vec<_>>();
+                for root in numerical {
+                    roots.push((root.re, root.im, match root.im == 0.0 {
+                        true  => self.real_from_f64(root.re)?,
+                        false => Number::Complex(Complex { real: root.re, imag: root.im }),
+                    }));
+                }
+                // Real roots first, in order
+                roots.sort_by(|a, b| (a.1 != 0.0).cmp(&(b.1 != 0.0)).then(a.0.total_cmp(&b.0)).then(a.1.total_cmp(&b.1)));
+                let roots = Matrix::from_rows(roots.into_iter().map(|(_, _, root)| vec![root]).collect()).ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NoRoots))?;
+                Ok(Number::Matrix(Box::new(roots)))
+            },
+            _ => Err(type_error),
+        }
+    }
+
+    // Cofactor expansion along the first row, exact for integers and fractions
+    fn determinant(&self, matrix: Vec<Number>, size: usize) -> ExecutionResultNumber {
+        if size == 1 {
+            return Ok(matrix[0].clone());
+        }
+        let mut determinant = Number::Integer(BigInt::from(0u8));
+        for column in 0..size {
+            let minor = matrix[size..].iter().enumerate()
+                .filter(|(i, _)| i % size != column)
+                .map(|(_, number)| number.clone())
+                .collect();
+            let term = self.field_arithmetic(matrix[column].clone(), BinOps::Multiply, self.determinant(minor, size - 1)?)?;
+            let binop = if column % 2 == 0 { BinOps::Addition } else { BinOps::Subtraction };
+            determinant = self.field_arithmetic(determinant, binop, term)?;
+        }
+        Ok(determinant)
+    }
+
+    fn to_f64(&self, number: &Number) -> Result<f64, RuntimeError> {
+        match number {
+            Number::Integer(int)  => int.to_f64(),
+            Number::Real(real)    => Some(real.to_f64()),
+            Number::Rational(rat) => rat.to_f64(),
+            Number::PiMultiple(coefficient) => coefficient.to_f64().map(|coefficient| coefficient * PI),
+            Number::Infinity(Sign::Minus) => Some(f64::NEG_INFINITY),
+            Number::Infinity(_)   => Some(f64::INFINITY),
+            Number::Complex(_) | Number::Symbolic(_) | Number::Matrix(_) | Number::Polynomial(_) | Number::Tuple(_) | Number::Set(_) => None,
+        }.ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError))
+    }
+
+    fn to_decimal(&self, number: &Number) -> Result<Real, RuntimeError> {
+        match number {
+            Number::Integer(int)  => Ok(Real::from(int.clone())),
+            Number::Real(real)    => Ok(real.clone()),
+            Number::Rational(rat) => Ok(Real::from(rat.numer().clone()).div(&Real::from(rat.denom().clone()), &self.context)),
+            Number::PiMultiple(_) => self.to_decimal(&self.collapse(number.clone())),
+            Number::Complex(_) | Number::Infinity(_) | Number::Symbolic(_) | Number::Matrix(_) | Number::Polynomial(_) | Number::Tuple(_) | Number::Set(_) => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        }
+    }
+
+    // Only the first 15 digits of an f64 can be trusted, \sin(\pi / 6) is 0.5 and not
+    // 0.49999999999999994
+    fn real_from_f64(&self, real: f64) -> ExecutionResultNumber {
+        let context = RealContext { digits: self.context.digits.min(f64::DIGITS as usize), ..self.context.clone() };
+        match Real::from_f64(real) {
+            Some(real) => Ok(Number::Real(real.round_significant(&context))),
+            None if real.is_nan() => Err(RuntimeError::new(self.line, RuntimeErrorTypes::Undefined)),
+            None => Ok(Number::Infinity(if real < 0.0 { Sign::Minus } else { Sign::Plus })),
+        }
+    }
+
+    fn to_integer(&self, number: &Number) -> Result<BigInt, RuntimeError> {
+        match number {
+            Number::Integer(int) => Ok(int.clone()),
+            _ => Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        }
+    }
+
+    // \frac{d}{dx} of an expression, evaluated at x when x has a value
+    fn eval_derivative(&self, args: Vec<Value>) -> ExecutionResultNumber {
+        let [expression, Value::Identifier(x)] = &args[..] else {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError));
+        };
+        // Of a polynomial p stays a polynomial
+        if let Value::Identifier(function) = expression {
+            if let (true, Number::Polynomial(polynomial)) = (self.function_definitions.contains_key(function), self.get_ident_val(function.clone())?) {
+                return Ok(Number::Polynomial(Box::new(polynomial.derivative())));
+            }
+        }
+        let derivative = self.clone().differentiate(expression.clone(), x)?;
+        if self.variables.contains_key(x) {
+            self.evaluate_value(derivative.to_value())
+        } else {
+            Ok(derivative.into_number())
+        }
+    }
+
+    // Consumes the scope, which becomes symbolic with x free
+    fn differentiate(mut self, value: Value, x: &Identifier) -> Result<Symbolic, RuntimeError> {
+        self.symbolic = true;
+        self.variables.remove(x);
+        let number = self.evaluate_value(value)?;
+        Symbolic::from_number(&number)
+            .and_then(|symbolic| symbolic.differentiate(x))
+            .ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotDifferentiable(x.clone())))
+    }
+
+    // \int f \, dx is an antiderivative. \int_a^b is exact when there is one and numerical otherwise
+    fn eval_integral(&self, args: Vec<Value>) -> ExecutionResultNumber {
+        let (integrand, x, bounds) = match &args[..] {
+            [integrand, Value::Identifier(x)] => (integrand, x, None),
+            [integrand, Value::Identifier(x), a, b] => (integrand, x, Some((a, b))),
+            _ => return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError)),
+        };
+        let antiderivative = self.clone().integrate(integrand.clone(), x);
+        let Some((a, b)) = bounds else {
+            return Ok(antiderivative?.into_number());
+        };
+        let (a, b) = (self.evaluate_value(a.clone())?, self.evaluate_value(b.clone())?);
+        let infinite = matches!(a, Number::Infinity(_)) || matches!(b, Number::Infinity(_));
+        if let (Ok(antiderivative), false) = (antiderivative, infinite) {
+            let at = |bound: &Number| {
+                let mut scope = self.clone();
+                scope.variables.insert(x.clone(), bound.clone());
+                scope.evaluate_value(antiderivative.to_value())
+            };
+            if let (Ok(upper), Ok(lower)) = (at(&b), at(&a)) {
+                return self.arithmetic(upper, BinOps::Subtraction, lower);
+            }
+        }
+        self.quadrature(integrand, x, &a, &b)
+    }
+
+    // Consumes the scope like differentiate
+    fn integrate(mut self, value: Value, x: &Identifier) -> Result<Symbolic, RuntimeError> {
+        self.symbolic = true;
+        self.variables.remove(x);
+        let number = self.evaluate_value(value)?;
+        Symbolic::from_number(&number)
+            .and_then(|symbolic| symbolic.integrate(x))
+            .ok_or(RuntimeError::new(self.line, RuntimeErrorTypes::NotIntegrable(x.clone())))
+    }
+
+    // Adaptive Simpson's rule in double precision, an infinite interval is mapped onto a finite one
+    fn quadrature(&self, integrand: &Value, x: &Identifier, a: &Number, b: &Number) -> ExecutionResultNumber {
+        let mut scope = self.clone();
+        let mut f = |t: f64| {
+            scope.variables.insert(x.clone(), self.real_from_f64(t)?);
+            let number = scope.evaluate_value(integrand.clone())?;
+            scope.to_f64(&number)
+        };
+        let (a, b) = (self.to_f64(a)?, self.to_f64(b)?);
+        let (sign, a, b) = if a > b { (-1.0, b, a) } else { (1.0, a, b) };
+        // The integrand is taken to vanish at infinity, where t reaches the end of its interval
+        let integral = match (a.is_infinite(), b.is_infinite()) {
+            (false, false) => adaptive_simpson(&mut f, a, b)?,
+            // x = a + t / (1 - t)
+            (false, true) => adaptive_simpson(&mut |t| if t >= 1.0 { Ok(0.0) } else { Ok(f(a + t / (1.0 - t))? / (1.0 - t).powi(2)) }, 0.0, 1.0)?,
+            // x = b - t / (1 - t)
+            (true, false) => adaptive_simpson(&mut |t| if t >= 1.0 { Ok(0.0) } else { Ok(f(b - t / (1.0 - t))? / (1.0 - t).powi(2)) }, 0.0, 1.0)?,
+            // x = t / (1 - t^2)
+            (true, true) => adaptive_simpson(&mut |t| {
+                if t.abs() >= 1.0 { Ok(0.0) } else { Ok(f(t / (1.0 - t * t))? * (1.0 + t * t) / (1.0 - t * t).powi(2)) }
+            }, -1.0, 1.0)?,
+        };
+        self.real_from_f64(sign * integral)
+    }
+
+    // Exact for rational functions, numerical otherwise
+    fn eval_limit(&self, limit: Limit) -> ExecutionResultNumber {
+        let approaches = self.evaluate_value(limit.approaches.clone())?;
+        let approach = match &approaches {
+            Number::Infinity(sign) => Some(Approach::Infinite(*sign)),
+            Number::Integer(int)   => Some(Approach::Finite(BigRational::from_integer(int.clone()))),
+            Number::Rational(rat)  => Some(Approach::Finite(rat.clone())),
+            Number::Real(real)     => Some(Approach::Finite(real.to_rational())),
+            _ => None,
+        };
+        let mut scope = ProgramInterpreter { symbolic: true, ..self.clone() };
+        scope.variables.remove(&limit.variable);
+        let rational = scope.evaluate_value(limit.value.clone()).ok()
+            .and_then(|number| Symbolic::from_number(&number))
+            .and_then(|symbolic| symbolic.rational_function(&limit.variable));
+        if let (Some((numerator, denominator)), Some(approach)) = (rational, approach) {
+            return limit::rational_limit(numerator, denominator, &approach, limit.side)
+                .map_err(|reason| RuntimeError::new(self.line, RuntimeErrorTypes::LimitDoesNotExist(reason)));
+        }
+        self.numerical_limit(&limit, &approaches)
+    }
+
+    // From each side asked for, the two have to agree
+    fn numerical_limit(&self, limit: &Limit, approaches: &Number) -> ExecutionResultNumber {
+        let a = self.to_f64(approaches)?;
+        let sides = match limit.side {
+            _ if a.is_infinite() => vec![Side::Both],
+            Side::Both => vec![Side::Left, Side::Right],
+            side => vec![side],
+        };
+        let mut scope = ProgramInterpreter { symbolic: false, ..self.clone() };
+        let mut reports = vec![];
+        for side in sides {
+            let mut samples = vec![];
+            for x in limit::sample_points(a, side) {
+                scope.variables.insert(limit.variable.clone(), self.real_from_f64(x)?);
+                let number = scope.evaluate_value(limit.value.clone())?;
+                samples.push((x, scope.to_f64(&number)?));
+            }
+            let values = samples.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+            let (estimate, error) = match limit::unbounded(&values) {
+                Some(infinity) => (infinity, f64::INFINITY),
+                None => limit::extrapolate(&values),
+            };
+            let report = ConvergenceReport { variable: limit.variable.clone(), side, samples, estimate, error };
+            if !report.converged() {
+                return Err(RuntimeError::new(self.line, RuntimeErrorTypes::LimitDoesNotExist(report.to_string())));
+            }
+            reports.push(report);
+        }
+        let estimate = match &reports[..] {
+            [left, right] if !left.agrees(right) => {
+                let reason = format!("it goes to {} from the left and {} from the right", left.estimate, right.estimate);
+                return Err(RuntimeError::new(self.line, RuntimeErrorTypes::LimitDoesNotExist(reason)));
+            },
+            [left, right] if left.estimate.is_finite() => (left.estimate + right.estimate) / 2.0,
+            reports => reports[0].estimate,
+        };
+        self.convergence_reports.borrow_mut().extend(reports);
+        self.real_from_f64(estimate)
+    }
+
+    // f'(x) without a definition of its own is the derivative of f, f'' the derivative of f' and so on
+    fn derived_function(&self, ident: &Identifier) -> Result<Option<FunctionDefinition>, RuntimeError> {
+        let Some(name) = (match ident { Identifier::TextIdent(name) => name.strip_suffix('\''), _ => None }) else {
+            return Ok(None);
+        };
+        let base = Identifier::TextIdent(name.to_owned());
+        let function = match self.function_definitions.get(&base) {
+            Some(function) => function.clone(),
+            None => match self.derived_function(&base)? {
+                Some(function) => function,
+                None => return Ok(None),
+            },
+        };
+        let [x] = &function.arguments[..] else {
+            return Err(RuntimeError::new(self.line, RuntimeErrorTypes::TypeError));
+        };
+        // The body only sees its arguments, the same as in interpret_function_call
+        let scope = ProgramInterpreter { context: self.context.clone(), line: self.line, ..Default::default() };
+        let derivative = scope.differentiate(Value::Expression(Box::new(function.expression)), x)?;
+        Ok(Some(FunctionDefinition {
+            identifier: ident.clone(),
+            arguments:  vec![x.clone()],
+            expression: Expression::Value(Box::new(derivative.to_value())),
+        }))
+    }
+
+    // f'(2) is a number, f'(x) with x free is the derivative itself
+    fn interpret_derived_call(&self, function_call: FunctionCall) -> ExecutionResultNumber {
+        let Some(definition) = self.derived_function(&function_call.function)? else {
+            return self.interpret_function_call(function_call);
+        };
+        let mut scope = self.clone();
+        let free = ProgramInterpreter { symbolic: true, ..self.clone() };
+        let args = function_call.args.into_iter()
+            .map(|value| self.evaluate_value(value.clone()).or_else(|_| free.evaluate_value(value)))
+            .collect::<Result<Vec<_>, _>>()?;
+        scope.symbolic |= args.iter().any(|number| matches!(number, Number::Symbolic(_)));
+        let function = function_call.function;
+        scope.function_declarations.insert(function.clone(), FunctionDeclaration { identifier: function.clone(), domain: Token::Any, codomain: Token::Any, memoized: false });
+        scope.function_definitions.insert(function.clone(), definition);
+        scope.interpret_function_call(FunctionCall { function, args: args.into_iter().map(Value::Number).collect() })
     }
 
     fn get_ident_val(&self, ident: Identifier) -> ExecutionResultNumber {
         if self.variables.contains_key(&ident.clone()) { Ok(self.variables[&ident.clone()].clone()) }
+        else if self.function_definitions.contains_key(&ident) { self.function_polynomial(&ident) }
+        else if let Identifier::SubScriptIdent(subscript) = &ident {
+            // a_n where a is a sequence and n is in scope
+            if !self.sequences.contains_key(&subscript.first_ident) {
+                return self.free_variable(ident);
+            }
+            let index = match &subscript.secnd_ident {
+                Identifier::NumberIdent(index) => Number::Integer(index.clone()),
+                index => self.get_ident_val(index.clone())?,
+            };
+            self.evaluate_sequence_term(&subscript.first_ident, index)
+        }
+        else if self.symbolic { self.free_variable(ident) }
+        else if let Some(number) = self.constant(&ident) { Ok(number) }
         else { Err(RuntimeError::new(self.line, RuntimeErrorTypes::MissingVariable(ident))) }
     }
+
+    // In symbolic mode anything without a value is a variable, the constants of the prelude included
+    fn free_variable(&self, ident: Identifier) -> ExecutionResultNumber {
+        if self.symbolic {
+            Ok(Number::Symbolic(Box::new(Symbolic::Variable(ident))))
+        } else {
+            Err(RuntimeError::new(self.line, RuntimeErrorTypes::MissingVariable(ident)))
+        }
+    }
+
+    // The prelude, only used when nothing in scope has the same name
+    fn constant(&self, ident: &Identifier) -> Option<Number> {
+        match ident {
+            Identifier::GreekLetter(GreekLetters::Pi) if self.exact => Some(Number::PiMultiple(BigRational::from_integer(BigInt::from(1u8)))),
+            Identifier::GreekLetter(GreekLetters::Pi)     => Some(Number::Real(Real::pi(&self.context))),
+            Identifier::GreekLetter(GreekLetters::VarPhi) => Some(Number::Real(Real::golden_ratio(&self.context))),
+            Identifier::TextIdent(name) if name == "e"    => Some(Number::Real(Real::e(&self.context))),
+            Identifier::TextIdent(name) if name == "i"    => Some(Number::Complex(Complex { real: 0.0, imag: 1.0 })),
+            _ => None,
+        }
+    }
 }
 
 impl Display for ProgramInterpreter {
@@ -359,3 +1595,26 @@ impl Display for ProgramInterpreter {
         write!(f, "Functions: {:?}\nVariables: {:?}\n", self.function_declarations, self.variables)
     }
 }
+
+type Integrand<'a> = dyn FnMut(f64) -> Result<f64, RuntimeError> + 'a;
+
+fn adaptive_simpson(f: &mut Integrand, a: f64, b: f64) -> Result<f64, RuntimeError> {
+    let m = (a + b) / 2.0;
+    let (fa, fm, fb) = (f(a)?, f(m)?, f(b)?);
+    let whole = (b - a) / 6.0 * (fa + 4.0 * fm + fb);
+    simpson_step(f, [(a, fa), (m, fm), (b, fb)], whole, 1e-12, 40)
+}
+
+// Splits [a, b] in half until both halves agree with the whole to within the tolerance
+fn simpson_step(f: &mut Integrand, [(a, fa), (m, fm), (b, fb)]: [(f64, f64); 3], whole: f64, tolerance: f64, depth: usize) -> Result<f64, RuntimeError> {
+    let (left_m, right_m) = ((a + m) / 2.0, (m + b) / 2.0);
+    let (f_left_m, f_right_m) = (f(left_m)?, f(right_m)?);
+    let left  = (m - a) / 6.0 * (fa + 4.0 * f_left_m + fm);
+    let right = (b - m) / 6.0 * (fm + 4.0 * f_right_m + fb);
+    let error = left + right - whole;
+    if depth == 0 || error.abs() <= 15.0 * tolerance {
+        return Ok(left + right + error / 15.0);
+    }
+    Ok(simpson_step(f, [(a, fa), (left_m, f_left_m), (m, fm)], left, tolerance / 2.0, depth - 1)?
+        + simpson_step(f, [(m, fm), (right_m, f_right_m), (b, fb)], right, tolerance / 2.0, depth - 1)?)
+}
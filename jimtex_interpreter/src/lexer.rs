@@ -1,5 +1,6 @@
 use std::{char, fs, path::Path};
-use crate::{ast::{BinOps, Conditionals, GreekLetters, Loops, Statements, UnOps}, ast_types::FunctionCall, parser::{Command, NewCommand}};
+use crate::{ast::{BinOps, Builtin, Conditionals, GreekLetters, Loops, Statements, UnOps}, ast_types::{BinaryOperation, BuiltinCall, FunctionCall, Identifier, Limit, NumberSet, SequenceTerm, UnaryOperation, Value}, parser::{Command, NewCommand}, real::Real};
+use crate::errors::ParseError;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -11,6 +12,10 @@ pub enum Token {
     Equals,
     Period,
     Newline,
+    Semicolon,
+    Pipe,
+    Exclamation,
+    // Only inside of \verb and verbatim, everywhere else % starts a comment
     Percent,
     Exponent,
     Backslash,
@@ -34,12 +39,17 @@ pub enum Token {
     CloseCodeDisplay,
     EscapedLeftBrace,
     EscapedOctothorpe,
+    EscapedPercent,
     EscapedRightBrace,
     FormatDoubleDollar,
 
     Text(String),
     Number(String),
     Operator(Operator),
+    // Everything after the %, the newline ending it included
+    Comment(String),
+    // %jimtex: directives, everything after the colon
+    Pragma(String),
 
     RealNumbers,
     NatrualNumbers,
@@ -61,12 +71,28 @@ pub enum Token {
     Loop(Loops),
     RightArrow,
     FunctionCall(FunctionCall),
+    Builtin(Builtin),
+    BuiltinCall(BuiltinCall),
+    NumberSet(Box<NumberSet>),
+    // \lvert and \rvert, \lfloor and \rfloor, ...
+    OpenDelimiter(UnOps),
+    CloseDelimiter(UnOps),
+    UnaryOperation(Box<UnaryOperation>),
+    BinaryOperation(Box<BinaryOperation>),
+    SequenceTerm(SequenceTerm),
+    Limit(Limit),
+    Matrix(Vec<Vec<Value>>),
+    Identifier(Identifier),
+    // Where parsing went wrong, the statement it ends up in fails when it runs
+    ParseError(ParseError),
 
-    Real(f64),
+    Real(Real),
+    Infinity,
 
     If,
     Then,
-    Else
+    Else,
+    Memo
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -89,20 +115,39 @@ pub fn lex(input: &Path) -> TokenString {
     let mut num_vec:  Vec<char> = vec![];
 
     let mut ignore = 0;
+    let mut verbatim: Option<(Vec<char>, usize)> = None;
 
     for (i, char) in input.chars().enumerate() {
         if ignore > 0 {
             ignore -= 1;
             continue;
         }
+        match &verbatim {
+            Some((end, from)) if i >= *from && input_vec[i..].starts_with(end) => verbatim = None,
+            None => verbatim = verbatim_start(&input_vec, i),
+            _ => (),
+        }
         match char {
             '\t' => token_string.push(Token::Tab),
             ','  => token_string.push(Token::Comma),
             ':'  => token_string.push(Token::Colon),
+            ';'  => token_string.push(Token::Semicolon),
+            '|'  => token_string.push(Token::Pipe),
+            '!'  => token_string.push(Token::Exclamation),
             ' '  => token_string.push(Token::Space),
             '.'  => token_string.push(Token::Period),
             '='  => token_string.push(Token::Equals),
-            '%'  => token_string.push(Token::Percent),
+            '%' if verbatim.is_some() => token_string.push(Token::Percent),
+            '%'  => {
+                // Comments run to the end of the line, like in LaTeX the newline is part of it
+                let end = input_vec[i..].iter().position(|char| *char == '\n').map_or(input_vec.len(), |newline| i + newline + 1);
+                let comment = input_vec[(i+1)..end].iter().collect::<String>();
+                ignore += end - i - 1;
+                match comment.strip_prefix("jimtex:") {
+                    Some(pragma) => token_string.push(Token::Pragma(pragma.to_owned())),
+                    None         => token_string.push(Token::Comment(comment)),
+                }
+            },
             '\n' => token_string.push(Token::Newline),
             '^'  => token_string.push(Token::Exponent),
             '_'  => token_string.push(Token::Subscript),
@@ -128,6 +173,7 @@ pub fn lex(input: &Path) -> TokenString {
                     '{' => { token_string.push(Token::EscapedLeftBrace); ignore += 1; },
                     '}' => { token_string.push(Token::EscapedRightBrace); ignore += 1; },
                     '#' => { token_string.push(Token::EscapedOctothorpe); ignore += 1; },
+                    '%' => { token_string.push(Token::EscapedPercent); ignore += 1; },
                     '$' => {
                         match input_vec.get(i+2).copied().unwrap_or_default() {
                             '(' => { token_string.push(Token::OpenCodeInline);   ignore += 1; },
@@ -138,12 +184,6 @@ pub fn lex(input: &Path) -> TokenString {
                         }
                         ignore += 1;
                     },
-                    // Basic Number Sets
-                    'Z' => { token_string.push(Token::Integers);        ignore += 1; },
-                    'R' => { token_string.push(Token::RealNumbers);     ignore += 1; },
-                    'Q' => { token_string.push(Token::RationalNumbers); ignore += 1; },
-                    'C' => { token_string.push(Token::ComplexNumbers);  ignore += 1; },
-                    'N' => { token_string.push(Token::NatrualNumbers);  ignore += 1; },
                     _   => token_string.push(Token::Backslash),
                 }
             },
@@ -154,22 +194,19 @@ pub fn lex(input: &Path) -> TokenString {
                 }
             }
             char => {
+                // Nothing was pushed since the text/number started, so it goes straight on the end
                 if char.is_ascii_digit() {
-                    num_vec.push(char);
                     if !text_vec.is_empty() {
-                        let prev: Token = token_string.pop().expect("Should be impossible");
                         token_string.push(Token::Text(text_vec.iter().collect::<String>()));
-                        token_string.push(prev);
                         text_vec = vec![];
                     }
+                    num_vec.push(char);
                 } else {
-                    text_vec.push(char);
                     if !num_vec.is_empty() {
-                        let prev: Token = token_string.pop().expect("Should be impossible");
                         token_string.push(Token::Number(num_vec.iter().collect::<String>()));
-                        token_string.push(prev);
                         num_vec = vec![];
                     }
+                    text_vec.push(char);
                 }
                 continue;
             }
@@ -187,5 +224,34 @@ pub fn lex(input: &Path) -> TokenString {
             num_vec = vec![];
         }
     }
+    if !text_vec.is_empty() {
+        token_string.push(Token::Text(text_vec.iter().collect::<String>()));
+    }
+    if !num_vec.is_empty() {
+        token_string.push(Token::Number(num_vec.iter().collect::<String>()));
+    }
     token_string
 }
+
+// \verb|...| and \begin{verbatim}, what ends it and where to start looking for the end
+fn verbatim_start(input: &[char], i: usize) -> Option<(Vec<char>, usize)> {
+    let starts_with = |text: &str| input[i..].starts_with(&text.chars().collect::<Vec<char>>());
+
+    for environment in ["verbatim", "lstlisting"] {
+        let begin = format!("\\begin{{{environment}}}");
+        if starts_with(&begin) {
+            return Some((format!("\\end{{{environment}}}").chars().collect(), i + begin.len()));
+        }
+    }
+    if starts_with("\\verb") {
+        let mut delimiter = i + 5;
+        if input.get(delimiter) == Some(&'*') {
+            delimiter += 1;
+        }
+        match input.get(delimiter) {
+            Some(char) if !char.is_ascii_alphabetic() && !char.is_whitespace() => return Some((vec![*char], delimiter + 1)),
+            _ => (),
+        }
+    }
+    None
+}
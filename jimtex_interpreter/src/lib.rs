@@ -9,15 +9,29 @@ pub mod ast_types;
 pub mod interpreter;
 pub mod errors;
 pub mod compiler;
+pub mod document;
+pub mod format;
+pub mod real;
+pub mod symbolic;
+pub mod limit;
+pub mod matrix;
+pub mod polynomial;
+pub mod modular;
+pub mod algebra;
 
 #[cfg(test)]
 mod tests {
     use core::panic;
+    use std::fs;
     use std::path::Path;
 
+    use crate::ast_types::{Declaration, Identifier, Number, Pragma, Statement, Value, ValueDeclaration};
+    use crate::compiler::compile;
+    use crate::document::{parse_document, Node};
+    use crate::errors::ParseError;
     use crate::interpreter::ProgramInterpreter;
-    use crate::lexer::lex;
-    use crate::parser::parse;
+    use crate::lexer::{lex, Token};
+    use crate::parser::{parse, parse_with_math, Environment};
     use crate::parser_ast::parse_to_ast;
 
     #[test]
@@ -70,4 +84,570 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_memo() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/memo_test.tex"));
+        let tokens = parse(tokens);
+        let program = parse_to_ast(tokens);
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "832040", "2880067194370816120", "18446744073709551616",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequence() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/sequence_test.tex"));
+        let tokens = parse(tokens);
+        let program = parse_to_ast(tokens);
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "832040", "5", "220", "62",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_subscript() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/subscript_test.tex"));
+        let tokens = parse(tokens);
+        let program = parse_to_ast(tokens);
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "26", "2", "17", "3",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_macros() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/macro_test.tex"));
+        let tokens = parse(tokens);
+        let program = parse_to_ast(tokens);
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec!["9", "7", "8", "12", "9", "7"]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+
+        // A macro defined in terms of itself or using an argument it doesn't take fails its
+        // statement instead of the whole parse
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/macro_error_test.tex"));
+        let mut interpreter = ProgramInterpreter::default();
+        let errors = parse_to_ast(parse(tokens)).program.into_iter()
+            .map(|statement| interpreter.evaluate_statement(statement).unwrap_err().to_string())
+            .collect::<Vec<_>>();
+        let expected = ["Expanding \\again goes too deep", "Macro \\f has no argument #0", "Macro \\g has no argument #3"];
+        assert_eq!(errors.len(), expected.len());
+        for (error, expected) in errors.iter().zip(expected) {
+            assert!(error.starts_with(expected), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_number_sets() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/number_set_test.tex"));
+        assert!(tokens.contains(&Token::Text("Rightarrow".to_owned())));
+        let tokens = parse(tokens);
+        let program = parse_to_ast(tokens);
+
+        let domains = program.program.iter().filter_map(|statement| match statement {
+            Statement::Declaration(Declaration::FunctionDeclaration(declaration)) => Some((declaration.domain.clone(), declaration.codomain.clone())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let sets = domains.iter().map(|(domain, codomain)| match (domain, codomain) {
+            (Token::NumberSet(domain), codomain) => format!("{domain} {codomain:?}"),
+            (domain, codomain) => format!("{domain:?} {codomain:?}"),
+        }).collect::<Vec<_>>();
+        assert_eq!(sets, vec![
+            "\\mathbb{Z}_{\\geq 0} NatrualNumbers",
+            "\\mathbb{R}^{n} RealNumbers",
+            "\\mathbb{Z}_{> 0} RationalNumbers",
+            "\\mathbb{R}^{3} ComplexNumbers",
+        ]);
+
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.interpret_program(program) {
+            Ok(_)      => (),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        for file in ["test_file.tex", "document_test.tex"] {
+            let path = format!("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/{file}");
+            let document = parse_document(lex(Path::new(&path)));
+            assert_eq!(document.to_string(), fs::read_to_string(&path).unwrap());
+        }
+
+        let document = parse_document(lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/document_test.tex")));
+        let Some(Node::Environment(body)) = document.nodes.iter().find(|node| matches!(node, Node::Environment(_))) else {
+            panic!("no document environment")
+        };
+        assert_eq!(body.environment, Environment::Document);
+        let environments = body.body.iter().filter_map(|node| match node {
+            Node::Environment(environment) => Some(environment.environment.clone()),
+            Node::Verbatim(_) => Some(Environment::Verbatim),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(environments, vec![Environment::Itemize, Environment::Verbatim, Environment::Align]);
+        assert_eq!(document.code_blocks().len(), 2);
+    }
+
+    #[test]
+    fn test_math() {
+        // Without \jimtexeval math is only evaluated when asked for
+        let document = Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/document_test.tex");
+        assert!(parse(lex(document)).len() < parse_with_math(lex(document), true).len());
+
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/math_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        // aligned inside of the equation is part of it, not a statement of its own
+        assert_eq!(program.program.len(), 7);
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec!["18", "11", "54"]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let path = Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/comment_test.tex");
+        let tokens = lex(path);
+        assert!(tokens.contains(&Token::EscapedPercent));
+        assert!(tokens.contains(&Token::Comment(" this is a comment, \\$[ not code \\$]\n".to_owned())));
+        assert_eq!(parse_document(tokens.clone()).to_string(), fs::read_to_string(path).unwrap());
+
+        let program = parse_to_ast(parse(tokens));
+        assert_eq!(program.program.iter().filter(|statement| matches!(statement, Statement::Pragma(_))).count(), 2);
+        assert!(program.program.contains(&Statement::Pragma(Pragma::Strict(true))));
+
+        // g is defined without a declaration after %jimtex: strict
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.interpret_program(program) {
+            Ok(_)      => panic!(),
+            Err(error) => eprintln!("{error}"),
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/compile_test.tex"));
+        match compile(tokens) {
+            Ok(compilation) => assert_eq!(compilation.latex, concat!(
+                "\\documentclass{article}\n",
+                "\\newcommand{\\sq}[1]{#1 * #1}\n",
+                "\\begin{document}\n",
+                "\\[\nf: \\Z \\rightarrow \\Z,\nf(x) = \\text{if } x \\text{ then } \\sq{x} + 1 \\text{ else } 0\n\\]\n",
+                "The answer is \\ensuremath{10}, and half of it is $\\ensuremath{5}$.\n",
+                "\\end{document}\n",
+            )),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        let path = Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/blocks_test.tex");
+        let source = fs::read_to_string(path).unwrap();
+
+        // x = 4 has no trailing comma
+        let program = parse_to_ast(parse(lex(path)));
+        assert_eq!(program.program.len(), 6);
+
+        let compilation = compile(lex(path)).unwrap();
+        assert!(compilation.latex.contains("Then \\ensuremath{5}, \\ensuremath{\\textbf{??}} and \\ensuremath{\\textbf{??}}."));
+        let blocks = compilation.blocks.iter().map(|block| (block.index, &source[block.range.clone()], block.result.as_ref().map(|values| values.len()).ok())).collect::<Vec<_>>();
+        assert_eq!(blocks, vec![
+            (0, "\\$[\nx = 4\n\\$]", Some(0)),
+            (1, "\\$( x + 1 \\$)", Some(1)),
+            (2, "\\$( y \\$)", None),
+            (3, "\\$( x = 5 \\$)", None),
+            (4, "\\$[\ny = x * 2,\ny\n\\$]", Some(1)),
+        ]);
+        for block in &compilation.blocks {
+            if let Err(error) = &block.result {
+                eprintln!("block {}: {error}", block.index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_separators() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/separator_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        assert_eq!(program.program.len(), 7);
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec!["30", "0", "10"]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/implicit_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec!["7", "12", "8", "8", "30", "20", "4", "20"]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_combinatorics() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/combinatorics_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "120", "7", "240", "7", "6", "2", "15", "3", "10", "2598960", "0", "60", "265252859812191058636308480000000",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/functions_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "0.0", "2.0", "0.0", "3.0", "1.0", "2.0", "3", "6", "60", "6", "7.5", "3", "2", "2", "-2", "7",
+                // Rounded to the digits an f64 gets right
+                "0.5", "1.5",
+                // Integers that don't divide make a fraction
+                "7/2", "7", "-19/6",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+
+        // Each of these fails on its own instead of stopping the parse
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/functions_error_test.tex"));
+        let mut interpreter = ProgramInterpreter::default();
+        let errors = parse_to_ast(parse(tokens)).program.into_iter()
+            .map(|statement| interpreter.evaluate_statement(statement).unwrap_err().to_string())
+            .collect::<Vec<_>>();
+        let expected = [
+            "Unbalanced delimiter", "Unbalanced delimiter", "Unbalanced delimiter", "Unbalanced delimiter", "Result is undefined",
+            "Result is undefined", "Result is undefined",
+            "Missing a value", "Missing a value", "Subscript with no index", "Subscript with no index", "Type Error",
+            "Unbalanced brackets",
+        ];
+        assert_eq!(errors.len(), expected.len());
+        for (error, expected) in errors.iter().zip(expected) {
+            assert!(error.starts_with(expected), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_constants() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/constants_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "314", "2718", "1618", "-1.0", "5.0", "0.0", "-1.0", "3", "10",
+                "5\\pi/2", "1", "0", "\\pi/2", "1.0",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+
+        // Strict documents can't shadow the prelude
+        let e = Declaration::ValueDeclaration(ValueDeclaration { identifier: Identifier::TextIdent("e".to_owned()), value: Value::Number(Number::Integer(1.into())) });
+        interpreter.evaluate_statement(Statement::Pragma(Pragma::Strict(true))).unwrap();
+        assert!(interpreter.evaluate_statement(Statement::Declaration(e)).is_err());
+    }
+
+    #[test]
+    fn test_reals() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/real_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        let values = match interpreter.evaluate_program(program) {
+            Ok(values) => values,
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        };
+        assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+            "15.6", "100000000000000000001.5", "0.333333333333333333333333333333", "0.0", "123456789012345678", "1.5",
+            "0.66667", "3.1416", "0.66666", "1.005",
+            // Printing rounds with the document's rounding mode
+            "1.00", "1.01", "15.60",
+        ]);
+    }
+
+    #[test]
+    fn test_format() {
+        let compilation = compile(lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/format_test.tex"))).unwrap();
+        let values = compilation.blocks[0].result.as_ref().unwrap();
+        assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+            "1,234,567", "7/2", "3 1/2", "3.5", "3.0 + 4.0i", "3.0 - i", "1.23e5", "1234.57",
+        ]);
+        // Inline blocks are typeset with the LaTeX renderer
+        assert!(compilation.latex.contains("So $\\ensuremath{3\\frac{1}{2}}$, $\\ensuremath{1.23 \\times 10^{6}}$ and $\\ensuremath{1{,}234{,}567}$."));
+        // Misspelled pragmas are skipped, an unknown \jimtexformat option fails its statement
+        assert_eq!(compilation.warnings.len(), 2);
+        assert!(compilation.warnings[0].contains("%jimtex: format fraction"));
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/format_error_test.tex"));
+        let error = ProgramInterpreter::default().evaluate_program(parse_to_ast(parse(tokens))).unwrap_err();
+        assert!(error.to_string().contains("Unknown format fraction"));
+    }
+
+    #[test]
+    fn test_symbolic() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/symbolic_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "1024", "512", "0.125", "4.0",
+                "2x", "2x + 2y", "3x + 2", "(x + 1)^2", "x^2 + 2x + 1", "a^2 - b^2",
+                "(x + 1)(x - 1)", "2x(x + 2)", "(x + 1)^2",
+                "\\frac{5x}{6}", "5/6", "x^2", "\\frac{1}{x + 1}", "y - 1",
+                "(y + 1)^2 + a", "10",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_derivative() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/derivative_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "x^2 \\cos x + 2x \\sin x", "2x y + 1", "6x", "2e^{2t}", "-\\frac{\\sin \\theta}{\\cos \\theta}",
+                // f' and f'' come from the definition of f
+                "3x^2 + 1", "13", "6x",
+                // At the value of x once it has one
+                "12",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_integral() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/integral_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "\\frac{x^3}{3}", "x^3 + x^2 + x", "\\frac{\\sin(2x)}{2}", "\\frac{e^{3t}}{3}", "\\ln |x|", "\\arctan x",
+                // Exact from the antiderivative where there is one
+                "1/3", "2.0", "1.0", "1",
+                // Numerical otherwise
+                "3.241309", "1.570796", "1.288226",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+
+        // Malformed integrals fail their own statement
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/integral_error_test.tex"));
+        let errors = parse_to_ast(parse(tokens)).program.into_iter()
+            .map(|statement| interpreter.evaluate_statement(statement).unwrap_err().to_string())
+            .collect::<Vec<_>>();
+        let expected = ["Integral with only one bound", "Integral with no differential", "Integral with no bound"];
+        assert_eq!(errors.len(), expected.len());
+        for (error, expected) in errors.iter().zip(expected) {
+            assert!(error.starts_with(expected), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_limit() {
+        let compilation = compile(lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/limit_test.tex"))).unwrap();
+        let values = compilation.blocks[0].result.as_ref().unwrap();
+        assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+            // Rational functions are exact
+            "2", "3", "-\\infty", "\\infty", "-\\infty", "6",
+            // Anything else is numerical
+            "1.000000", "2.718282", "1.000000", "-\\infty",
+        ]);
+        let errors = compilation.blocks[1..].iter().map(|block| block.result.as_ref().unwrap_err().to_string()).collect::<Vec<_>>();
+        assert!(errors[0].starts_with("Limit does not exist, it goes to -\\infty from the left and \\infty from the right"));
+        // The samples that didn't converge are part of the error
+        assert!(errors[1].contains("x = -1e-1: 0.54402111088937") && errors[1].contains("didn't converge"));
+        // Limits that can't be parsed
+        assert!(errors[2].starts_with("Limit with no subscript"));
+        assert!(errors[3].starts_with("Limit with no \\to"));
+    }
+
+    #[test]
+    fn test_matrix() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/matrix_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "\\begin{pmatrix} 1 & 3 \\\\ 2 & 4 \\end{pmatrix}",
+                "\\begin{pmatrix} -2 & 1 \\\\ -4 & 3 \\end{pmatrix}",
+                "\\begin{pmatrix} 2 & 3 \\\\ 7 & 8 \\end{pmatrix}",
+                "\\begin{pmatrix} 1 & 3 \\\\ 2 & 4 \\end{pmatrix}",
+                // Inverses and row reduction are exact
+                "\\begin{pmatrix} -2 & 1 \\\\ 3/2 & -1/2 \\end{pmatrix}",
+                "\\begin{pmatrix} 1 & 0 \\\\ 0 & 1 \\end{pmatrix}",
+                "\\begin{pmatrix} 1 & 0 \\\\ 0 & 1 \\end{pmatrix}",
+                "-2", "0", "14",
+                "\\begin{pmatrix} 1 & -2 & 3 \\end{pmatrix}",
+                "2",
+                "\\begin{pmatrix} 1 & 0 & -1 \\\\ 0 & 1 & 2 \\end{pmatrix}",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+
+        // Its statement fails, not the whole parse
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/matrix_error_test.tex"));
+        let error = ProgramInterpreter::default().evaluate_program(parse_to_ast(parse(tokens))).unwrap_err();
+        assert!(error.to_string().starts_with("\\begin{pmatrix} with no \\end"));
+    }
+
+    #[test]
+    fn test_polynomial() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/polynomial_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "x^3 - 2x + 1", "x^3 + x^2 - 2x", "x^5 - 3x^3 + x^2 + 2x - 1",
+                // Division with remainder, then the gcd
+                "x", "-x + 1", "x - 1",
+                "3x^2 - 2", "5",
+                // Over \\Q, including factors without rational roots
+                "(x - 1)(x^2 + x - 1)", "(x^2 + 2x + 2)(x^2 - 2x + 2)",
+                // Coefficients wrap around over \\Z/5\\Z
+                "x^4 + 3x^3 + 2x^2 + 4x + 4",
+                "\\begin{pmatrix} 2 \\\\ 4 \\end{pmatrix}",
+                "\\begin{pmatrix} -1.618034 \\\\ 0.618034 \\\\ 1 \\end{pmatrix}",
+                "\\begin{pmatrix} -1.000000 - i \\\\ -1.000000 + i \\\\ 1.000000 - i \\\\ 1.000000 + i \\end{pmatrix}",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_modular() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/modular_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                // Functions over \\Z/7\\Z wrap, dividing by 2 is multiplying by 4
+                "4", "1", "5",
+                "2", "2", "5", "1",
+                // Congruences are 1 or 0
+                "1", "0", "1", "1",
+                "1", "0",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_group() {
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/group_test.tex"));
+        let program = parse_to_ast(parse(tokens));
+        let mut interpreter = ProgramInterpreter::default();
+        match interpreter.evaluate_program(program) {
+            Ok(values) => assert_eq!(values.iter().map(|value| value.to_string()).collect::<Vec<_>>(), vec![
+                "6", "4", "3", "4", "2",
+                // A subgroup, its coset and the internal direct product with another
+                "\\{0, 2, 4\\}", "\\{1, 3, 5\\}", "\\{0, 1, 2, 3, 4, 5\\}", "\\{1, 4\\}",
+                "16", "36", "6",
+            ]),
+            Err(error) => {
+                eprintln!("{error}");
+                panic!()
+            }
+        }
+
+        // Subtraction isn't associative
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/group_error_test.tex"));
+        let error = ProgramInterpreter::default().evaluate_program(parse_to_ast(parse(tokens))).unwrap_err();
+        assert!(error.to_string().contains("associativity fails for 0, 0 and 1"));
+
+        // A set on its own isn't a value, it fails its statement instead of the parse
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/set_error_test.tex"));
+        assert_eq!(parse_to_ast(parse(tokens)).program, vec![Statement::Invalid(ParseError::BareSet); 3]);
+
+        // \langle a \rangle needs its group and its \rangle
+        let tokens = lex(Path::new("/home/lordgoatius/git/jimtex/jimtex_interpreter/src/subgroup_error_test.tex"));
+        let mut interpreter = ProgramInterpreter::default();
+        let errors = parse_to_ast(parse(tokens)).program.into_iter()
+            .map(|statement| interpreter.evaluate_statement(statement).unwrap_err().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("\\langle \\rangle without the group as a subscript"));
+        assert!(errors[1].starts_with("Unbalanced delimiter"));
+    }
 }
@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use num::bigint::Sign;
+use num::{BigRational, Signed, Zero};
+
+use crate::ast_types::{Identifier, Number, Side};
+use crate::polynomial::{divide_root, evaluate, trim};
+use crate::symbolic::Symbolic;
+
+// How close the last estimates of a numerical limit have to be, relative to its size
+const TOLERANCE: f64 = 1e-9;
+// And how close the limits from either side, which have been extrapolated separately
+const SIDES_TOLERANCE: f64 = 1e-6;
+// The last sample still has to be near the estimate, so noise that happens to extrapolate
+// to something consistent isn't taken for convergence
+const SAMPLE_TOLERANCE: f64 = 1e-3;
+
+// Where a limit goes, exact so that rational functions can be cancelled
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approach {
+    Finite(BigRational),
+    Infinite(Sign),
+}
+
+// The points a numerical limit was evaluated at, and what it made of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceReport {
+    pub variable: Identifier,
+    pub side:     Side,
+    pub samples:  Vec<(f64, f64)>,
+    pub estimate: f64,
+    // Difference between the two best extrapolations, infinite when the samples blow up
+    pub error:    f64,
+}
+
+impl ConvergenceReport {
+    pub fn converged(&self) -> bool {
+        let scale = self.estimate.abs().max(1.0);
+        let last = self.samples.last().map_or(f64::NAN, |(_, value)| *value);
+        self.estimate.is_infinite() || (self.error <= TOLERANCE * scale && (last - self.estimate).abs() <= SAMPLE_TOLERANCE * scale)
+    }
+
+    pub fn agrees(&self, other: &Self) -> bool {
+        self.estimate == other.estimate || (self.estimate - other.estimate).abs() <= SIDES_TOLERANCE * self.estimate.abs().max(1.0)
+    }
+}
+
+// x = 0.1: 0.998334, x = 0.01: 0.999983, ... converged to 1.0 within 1.1e-16
+impl Display for ConvergenceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let side = match self.side {
+            Side::Left  => " from the left",
+            Side::Right => " from the right",
+            Side::Both  => "",
+        };
+        let samples = self.samples.iter().map(|(x, value)| format!("{} = {x:e}: {value}", self.variable)).collect::<Vec<_>>();
+        write!(f, "{}{side}, ", samples.join(", "))?;
+        match (self.estimate.is_infinite(), self.converged()) {
+            (true, _)      => write!(f, "unbounded towards {}", self.estimate),
+            (false, true)  => write!(f, "converged to {} within {:e}", self.estimate, self.error),
+            (false, false) => write!(f, "didn't converge, the last estimates differ by {:e}", self.error),
+        }
+    }
+}
+
+// The sample points, 10^{-k} away from a or 10^k for \infty
+pub fn sample_points(a: f64, side: Side) -> Vec<f64> {
+    (1..=8).map(|k| {
+        let h = 10f64.powi(-k);
+        match side {
+            _ if a.is_infinite() => a.signum() / h,
+            Side::Left => a - h,
+            _          => a + h,
+        }
+    }).collect()
+}
+
+// Richardson extrapolation, assuming the error shrinks like a power series in the distance h
+// which is divided by 10 each sample. The estimate is the pair of extrapolations that agree best
+pub fn extrapolate(values: &[f64]) -> (f64, f64) {
+    let mut table: Vec<Vec<f64>> = vec![];
+    let mut best = (values[values.len() - 1], f64::INFINITY);
+    for (k, value) in values.iter().enumerate() {
+        let mut row = vec![*value];
+        for j in 1..=k {
+            let previous = &table[k - 1];
+            let extrapolated = row[j - 1] + (row[j - 1] - previous[j - 1]) / (10f64.powi(j as i32) - 1.0);
+            let error = (extrapolated - row[j - 1]).abs();
+            if error.is_finite() && error < best.1 {
+                best = (extrapolated, error);
+            }
+            row.push(extrapolated);
+        }
+        table.push(row);
+    }
+    best
+}
+
+// Samples that keep growing in size by steps that don't shrink, like 1/x or \ln x near 0
+pub fn unbounded(values: &[f64]) -> Option<f64> {
+    let last = &values[values.len() - 4..];
+    let sign = last[0].signum();
+    let growing = last.windows(2).all(|pair| pair[1].signum() == sign && pair[1].abs() > pair[0].abs());
+    let steps = last.windows(2).map(|pair| (pair[1] - pair[0]).abs()).collect::<Vec<_>>();
+    let not_shrinking = steps.windows(2).all(|pair| pair[1] >= 0.9 * pair[0]);
+    (growing && not_shrinking).then_some(sign * f64::INFINITY)
+}
+
+// The limit of p(x) / q(x) with coefficients lowest degree first, cancelling common factors of x - a.
+// The reason there's no limit otherwise
+pub fn rational_limit(mut numerator: Vec<BigRational>, mut denominator: Vec<BigRational>, approach: &Approach, side: Side) -> Result<Number, String> {
+    if denominator.iter().all(Zero::is_zero) {
+        return Err("the denominator is 0".to_owned());
+    }
+    if numerator.iter().all(Zero::is_zero) {
+        return Ok(Number::Integer(0.into()));
+    }
+    let infinity = |positive: bool| Number::Infinity(if positive { Sign::Plus } else { Sign::Minus });
+    let a = match approach {
+        Approach::Finite(a) => a,
+        Approach::Infinite(sign) => {
+            trim(&mut numerator);
+            trim(&mut denominator);
+            let (Some(n), Some(d)) = (numerator.last(), denominator.last()) else {
+                return Ok(Symbolic::Constant(BigRational::zero()).into_number());
+            };
+            let degree = numerator.len() as i64 - denominator.len() as i64;
+            return Ok(match degree {
+                ..0 => Symbolic::Constant(BigRational::zero()).into_number(),
+                0   => Symbolic::Constant(n / d).into_number(),
+                // x^3 goes to -\infty with x, x^2 doesn't
+                _   => infinity((n / d).is_positive() != (*sign == Sign::Minus && degree % 2 == 1)),
+            });
+        },
+    };
+    while evaluate(&numerator, a).is_zero() && evaluate(&denominator, a).is_zero() && !numerator.iter().all(Zero::is_zero) {
+        numerator = divide_root(&numerator, a);
+        denominator = divide_root(&denominator, a);
+    }
+    if !evaluate(&denominator, a).is_zero() {
+        return Ok(Symbolic::Constant(evaluate(&numerator, a) / evaluate(&denominator, a)).into_number());
+    }
+    // A pole, q(x) = (x - a)^k r(x) with r(a) not 0
+    let mut k = 0;
+    while evaluate(&denominator, a).is_zero() {
+        denominator = divide_root(&denominator, a);
+        k += 1;
+    }
+    let right = (evaluate(&numerator, a) / evaluate(&denominator, a)).is_positive();
+    let left = right == (k % 2 == 0);
+    match side {
+        Side::Right => Ok(infinity(right)),
+        Side::Left  => Ok(infinity(left)),
+        Side::Both if left == right => Ok(infinity(right)),
+        Side::Both => Err(format!("it goes to {} from the left and {} from the right", infinity(left), infinity(right))),
+    }
+}
@@ -0,0 +1,60 @@
+use std::hash::{Hash, Hasher};
+
+use num::BigInt;
+
+use crate::ast_types::Number;
+
+// \begin{pmatrix} 1 & 2 \\ 3 & 4 \end{pmatrix}, a vector is a matrix with one column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub rows:    usize,
+    pub columns: usize,
+    // Row by row
+    pub entries: Vec<Number>,
+}
+
+impl Matrix {
+    // None when the rows aren't all the same length
+    pub fn from_rows(rows: Vec<Vec<Number>>) -> Option<Self> {
+        let columns = rows.first()?.len();
+        if rows.iter().any(|row| row.len() != columns) {
+            return None;
+        }
+        Some(Self { rows: rows.len(), columns, entries: rows.into_iter().flatten().collect() })
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let entries = (0..size * size).map(|i| Number::Integer(BigInt::from((i / size == i % size) as u8))).collect();
+        Self { rows: size, columns: size, entries }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> &Number {
+        &self.entries[row * self.columns + column]
+    }
+
+    pub fn row(&self, row: usize) -> &[Number] {
+        &self.entries[row * self.columns..(row + 1) * self.columns]
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.columns
+    }
+
+    pub fn transpose(&self) -> Self {
+        let entries = (0..self.columns).flat_map(|column| (0..self.rows).map(move |row| (row, column)))
+            .map(|(row, column)| self.get(row, column).clone())
+            .collect();
+        Self { rows: self.columns, columns: self.rows, entries }
+    }
+
+    pub fn map(&self, f: impl FnMut(&Number) -> Number) -> Self {
+        Self { rows: self.rows, columns: self.columns, entries: self.entries.iter().map(f).collect() }
+    }
+}
+
+impl Hash for Matrix {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rows.hash(state);
+        self.entries.hash(state);
+    }
+}
@@ -0,0 +1,18 @@
+use num::{BigInt, BigRational, Integer, One, Signed};
+
+// a^{-1} mod n when a and n are coprime
+pub fn inverse(a: &BigInt, n: &BigInt) -> Option<BigInt> {
+    let gcd = a.extended_gcd(n);
+    gcd.gcd.is_one().then(|| gcd.x.mod_floor(n))
+}
+
+// a^k mod n by repeated squaring, a negative k is a power of the inverse
+pub fn power(a: &BigInt, k: &BigInt, n: &BigInt) -> Option<BigInt> {
+    let base = if k.is_negative() { inverse(a, n)? } else { a.mod_floor(n) };
+    Some(base.modpow(&k.abs(), n))
+}
+
+// p/q is p q^{-1} mod n
+pub fn residue(rational: &BigRational, n: &BigInt) -> Option<BigInt> {
+    inverse(rational.denom(), n).map(|inverse| (rational.numer() * inverse).mod_floor(n))
+}
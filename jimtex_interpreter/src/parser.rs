@@ -1,16 +1,25 @@
-use crate::lexer::{TokenString, Token};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::document::{children, parse_document, render_tokens, Argument, CodeBlock, Node};
+use crate::lexer::{Operator, TokenString, Token};
 use crate::ast::*;
+use crate::ast_types::{Identifier, Number, NumberSet, Value};
+use crate::errors::ParseError;
+
+const MAX_MACRO_DEPTH: usize = 64;
 
 // TODO:
 // Parse commands options ({},[])
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Command {
-    name: String,
-    req: Vec<TokenString>,
-    opt: Vec<TokenString>
+    pub(crate) name: String,
+    pub(crate) req: Vec<TokenString>,
+    pub(crate) opt: Vec<TokenString>
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Environment {
     Center, 
     Tabular,
@@ -18,17 +27,75 @@ pub enum Environment {
     Array,
     EqnArray,
     Equation,
-    Matrix
+    Matrix,
+    Align,
+    Gather,
+    Document,
+    Itemize,
+    Enumerate,
+    Verbatim,
+    Other(String),
+}
+
+impl Environment {
+    // Starred environments are the same environment, align* is still align
+    pub fn from_name(name: &str) -> Self {
+        match name.trim_end_matches('*') {
+            "center"       => Environment::Center,
+            "tabular"      => Environment::Tabular,
+            "math"         |
+            "displaymath"  => Environment::Math,
+            "array"        => Environment::Array,
+            "eqnarray"     => Environment::EqnArray,
+            "equation"     => Environment::Equation,
+            "matrix"       |
+            "pmatrix"      |
+            "bmatrix"      |
+            "Bmatrix"      |
+            "vmatrix"      |
+            "Vmatrix"      |
+            "smallmatrix"  => Environment::Matrix,
+            "align"        |
+            "aligned"      |
+            "alignat"      |
+            "flalign"      => Environment::Align,
+            "gather"       |
+            "gathered"     |
+            "multline"     => Environment::Gather,
+            "document"     => Environment::Document,
+            "itemize"      => Environment::Itemize,
+            "enumerate"    => Environment::Enumerate,
+            "verbatim"     |
+            "lstlisting"   => Environment::Verbatim,
+            other          => Environment::Other(other.to_owned()),
+        }
+    }
+
+    pub fn is_math(&self) -> bool {
+        matches!(self, Environment::Math | Environment::Array | Environment::EqnArray | Environment::Equation | Environment::Matrix | Environment::Align | Environment::Gather)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct NewCommand {
     cmd:  Command,
     args: u8,
-    def:  TokenString
+    def:  TokenString,
+    // \providecommand, doesn't replace an existing definition
+    provided: bool,
+    // The definition exactly as it was written
+    source: String,
 }
 
-fn make_commands(tokens: TokenString) -> TokenString {
+// Written back the way it was defined, so code block ranges line up with the source
+impl Display for NewCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+// \name becomes a CommandStub, builtin commands are only recognised per code block
+pub(crate) fn make_command_stubs(tokens: TokenString) -> TokenString {
     let mut ret_vec = vec![];
     let input_vec = tokens.clone();
 
@@ -41,17 +108,229 @@ fn make_commands(tokens: TokenString) -> TokenString {
 
         match token {
             Token::Backslash => {
-                if let Token::Text(text) = &input_vec[i+1] {
-                    ret_vec.push(Token::CommandStub(text.to_string()));
-                    ignore += 1;
-                } else {
-                    ret_vec.push(Token::Backslash);
+                // Command names are only letters, \verb|x| is \verb followed by |x|
+                match input_vec.get(i+1) {
+                    Some(Token::Text(text)) if text.starts_with(|char: char| char.is_ascii_alphabetic()) => {
+                        let (name, rest) = text.split_at(text.find(|char: char| !char.is_ascii_alphabetic()).unwrap_or(text.len()));
+                        ret_vec.push(Token::CommandStub(name.to_string()));
+                        if !rest.is_empty() {
+                            ret_vec.push(Token::Text(rest.to_string()));
+                        }
+                        ignore += 1;
+                    },
+                    _ => ret_vec.push(Token::Backslash),
                 }
             }
             catch => ret_vec.push(catch)
         }
     }
-    ret_vec.into_iter().map(|token| parse_cmd_stub(token)).collect()
+    make_new_commands(ret_vec)
+}
+
+// Macros can be defined anywhere in the document
+fn make_new_commands(tokens: TokenString) -> TokenString {
+    let mut ret_vec = vec![];
+
+    let mut i = 0;
+    while i < tokens.len() {
+        if let Some((new_command, offset)) = parse_new_command(&tokens, i) {
+            ret_vec.push(Token::NewCommand(new_command));
+            i += offset;
+        } else {
+            ret_vec.push(tokens[i].clone());
+            i += 1;
+        }
+    }
+    ret_vec
+}
+
+pub(crate) type Macros = HashMap<String, NewCommand>;
+
+pub(crate) fn define_macro(macros: &mut Macros, new_command: &NewCommand) {
+    // \providecommand never overwrites
+    if !(new_command.provided && macros.contains_key(&new_command.cmd.name)) {
+        macros.insert(new_command.cmd.name.clone(), new_command.clone());
+    }
+}
+
+// Only code blocks get expanded, macros in the rest of the document are left for LaTeX
+pub(crate) fn expand_macros(tokens: TokenString, macros: &Macros) -> TokenString {
+    let mut ret_vec = vec![];
+
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::CommandStub(name) if macros.contains_key(name) => match expand_macro(macros, &tokens, i, 0) {
+                Ok((expansion, offset)) => {
+                    ret_vec.extend(expansion);
+                    i += offset;
+                },
+                // Left in place of the macro for its statement to fail with
+                Err(error) => {
+                    ret_vec.push(Token::ParseError(error));
+                    i += 1;
+                },
+            },
+            token => {
+                ret_vec.push(token.clone());
+                i += 1;
+            },
+        }
+    }
+    ret_vec
+}
+
+fn skip_spaces(tokens: &TokenString, mut i: usize) -> usize {
+    while let Some(Token::Space | Token::Newline | Token::Tab) = tokens.get(i) {
+        i += 1;
+    }
+    i
+}
+
+// \newcommand{\name}[args][default]{def}, \renewcommand, \providecommand and \def\name#1#2{def}
+// Returns the definition and how many tokens it took up
+fn parse_new_command(tokens: &TokenString, begin: usize) -> Option<(NewCommand, usize)> {
+    let Some(Token::CommandStub(definer)) = tokens.get(begin) else {
+        return None;
+    };
+
+    let mut i = begin + 1;
+    let mut args = 0;
+    let mut opt  = vec![];
+
+    let name = match &definer[..] {
+        "newcommand" | "renewcommand" | "providecommand" => {
+            // Starred variants only differ for LaTeX's paragraph handling
+            if let Some(Token::Operator(Operator::Mul)) = tokens.get(i) {
+                i += 1;
+            }
+            i = skip_spaces(tokens, i);
+            let name = match (tokens.get(i), tokens.get(i+1), tokens.get(i+2)) {
+                (Some(Token::LeftBrace), Some(Token::CommandStub(name)), Some(Token::RightBrace)) => { i += 3; name.clone() },
+                (Some(Token::CommandStub(name)), _, _) => { i += 1; name.clone() },
+                _ => return None,
+            };
+            i = skip_spaces(tokens, i);
+            if let (Some(Token::LeftBracket), Some(Token::Number(number)), Some(Token::RightBracket)) = (tokens.get(i), tokens.get(i+1), tokens.get(i+2)) {
+                args = number.parse().ok()?;
+                i += 3;
+            }
+            i = skip_spaces(tokens, i);
+            if let Some(Token::LeftBracket) = tokens.get(i) {
+                let (offset, default) = find_inner_string_opt(tokens, i);
+                opt.push(default);
+                i += offset;
+            }
+            name
+        },
+        "def" => {
+            let Some(Token::CommandStub(name)) = tokens.get(i) else {
+                return None;
+            };
+            i += 1;
+            while let (Some(Token::Octothorpe), Some(Token::Number(_))) = (tokens.get(i), tokens.get(i+1)) {
+                args += 1;
+                i += 2;
+            }
+            name.clone()
+        },
+        _ => return None,
+    };
+
+    i = skip_spaces(tokens, i);
+    if tokens.get(i) != Some(&Token::LeftBrace) || args > 9 {
+        return None;
+    }
+    let (offset, def) = find_inner_string_req(tokens, i);
+    i += offset;
+
+    let provided = definer == "providecommand";
+    let source = render_tokens(&tokens[begin..i]);
+    Some((NewCommand { cmd: Command { name, req: vec![], opt }, args, def, provided, source }, i - begin))
+}
+
+// Returns the expanded tokens and how many tokens the use of the macro took up
+fn expand_macro(macros: &Macros, tokens: &TokenString, begin: usize, depth: usize) -> Result<(TokenString, usize), ParseError> {
+    let Some(Token::CommandStub(name)) = tokens.get(begin) else {
+        unreachable!()
+    };
+    if depth > MAX_MACRO_DEPTH {
+        return Err(ParseError::MacroTooDeep(name.clone()));
+    }
+    let new_command = &macros[name];
+
+    let mut i = begin + 1;
+    let mut args: Vec<TokenString> = vec![];
+    for arg in 0..new_command.args {
+        i = skip_spaces(tokens, i);
+        match (tokens.get(i), new_command.cmd.opt.first()) {
+            // Only the first argument can be optional
+            (Some(Token::LeftBracket), Some(_)) if arg == 0 => {
+                let (offset, value) = find_inner_string_opt(tokens, i);
+                args.push(value);
+                i += offset;
+            },
+            (_, Some(default)) if arg == 0 => args.push(default.clone()),
+            (Some(Token::LeftBrace), _) => {
+                let (offset, value) = find_inner_string_req(tokens, i);
+                args.push(value);
+                i += offset;
+            },
+            (Some(token), _) => {
+                args.push(vec![token.clone()]);
+                i += 1;
+            },
+            (None, _) => return Err(ParseError::MissingMacroArguments(name.clone())),
+        }
+    }
+
+    // Substitute #1..#9, ## is a literal # for nested definitions
+    let mut substituted = vec![];
+    let mut j = 0;
+    while j < new_command.def.len() {
+        match (&new_command.def[j], new_command.def.get(j+1)) {
+            (Token::Octothorpe, Some(Token::Number(number))) => {
+                let (param, rest) = number.split_at(1);
+                let param: usize = param.parse().unwrap();
+                // #0, or #3 in a macro that only takes one argument
+                let Some(arg) = param.checked_sub(1).and_then(|index| args.get(index)) else {
+                    return Err(ParseError::InvalidMacroParameter(name.clone(), param));
+                };
+                substituted.extend(arg.iter().cloned());
+                if !rest.is_empty() {
+                    substituted.push(Token::Number(rest.to_owned()));
+                }
+                j += 2;
+            },
+            (Token::Octothorpe, Some(Token::Octothorpe)) => {
+                substituted.push(Token::Octothorpe);
+                j += 2;
+            },
+            (token, _) => {
+                substituted.push(token.clone());
+                j += 1;
+            },
+        }
+    }
+
+    // Macros used inside of the definition
+    let mut expansion = vec![];
+    let mut j = 0;
+    while j < substituted.len() {
+        match &substituted[j] {
+            Token::CommandStub(name) if macros.contains_key(name) => {
+                let (inner, offset) = expand_macro(macros, &substituted, j, depth + 1)?;
+                expansion.extend(inner);
+                j += offset;
+            },
+            token => {
+                expansion.push(token.clone());
+                j += 1;
+            },
+        }
+    }
+
+    Ok((expansion, i - begin))
 }
 
 fn find_inner_string_req(token_str: &TokenString, begin: usize) -> (usize, TokenString) {
@@ -66,7 +345,7 @@ fn find_inner_string_req(token_str: &TokenString, begin: usize) -> (usize, Token
         }
         offset += 1;
     }
-    (offset, token_str[(begin+1)..(begin+offset-1)].iter().map(|token| token.clone()).collect())
+    (offset, token_str[(begin+1)..(begin+offset-1)].to_vec())
 }
 
 fn find_inner_string_opt(token_str: &TokenString, begin: usize) -> (usize, TokenString) {
@@ -81,7 +360,7 @@ fn find_inner_string_opt(token_str: &TokenString, begin: usize) -> (usize, Token
         }
         offset += 1;
     }
-    (offset, token_str[(begin+1)..(begin+offset-1)].iter().map(|token| token.clone()).collect())
+    (offset, token_str[(begin+1)..(begin+offset-1)].to_vec())
 }
 
 enum TypeArg {
@@ -123,7 +402,7 @@ fn command_option_parser(tokens: TokenString) -> TokenString {
                 }
             }
 
-            ret_vec.push(Token::Command(Command { 
+            ret_vec.push(parse_command(Command { 
                 name, 
                 req: req.into_iter().map(command_option_parser).collect(), 
                 opt: opt.into_iter().map(command_option_parser).collect()
@@ -136,26 +415,170 @@ fn command_option_parser(tokens: TokenString) -> TokenString {
     ret_vec
 }
 
-fn filter_what_gets_interpreted(tokens: TokenString) -> TokenString {
-    let mut ret = vec![];
+// Commands that only mean something once their arguments are known
+fn parse_command(command: Command) -> Token {
+    match (&command.name[..], &command.req[..]) {
+        ("mathbb", [set]) => {
+            match &set[..] {
+                [Token::Text(letter)] => {
+                    match &letter[..] {
+                        "Z" => Token::Integers,
+                        "R" => Token::RealNumbers,
+                        "Q" => Token::RationalNumbers,
+                        "C" => Token::ComplexNumbers,
+                        "N" => Token::NatrualNumbers,
+                        _   => Token::Command(command),
+                    }
+                },
+                _ => Token::Command(command),
+            }
+        },
+        // \operatorname{lcm}
+        ("operatorname", [name]) => {
+            match &name[..] {
+                [Token::Text(name)] => Builtin::from_name(name).map(Token::Builtin).unwrap_or(Token::Command(command)),
+                _ => Token::Command(command),
+            }
+        },
+        _ => Token::Command(command),
+    }
+}
 
-    let mut in_extex_envmt = false;
-    let mut comment = false;
+fn is_number_set(token: &Token) -> bool {
+    matches!(token, Token::Integers | Token::RealNumbers | Token::RationalNumbers | Token::ComplexNumbers | Token::NatrualNumbers)
+}
 
-    for token in tokens.into_iter() {
-        if in_extex_envmt && !comment {
-            ret.push(token.clone());
+// \Z_{\geq 0}, \R_{>0}, \Z^+
+fn parse_restriction(tokens: &[Token]) -> Option<(Conditionals, Number)> {
+    let zero = Number::Integer(0.into());
+    match tokens {
+        [Token::Operator(Operator::Add)] => Some((Conditionals::Greater, zero)),
+        [Token::Operator(Operator::Sub)] => Some((Conditionals::Less, zero)),
+        [Token::Conditional(condition), Token::Number(bound)] => Some((condition.clone(), Number::Integer(bound.parse().ok()?))),
+        [Token::Text(cmp), Token::Number(bound)] => {
+            let condition = match &cmp[..] {
+                ">" => Conditionals::Greater,
+                "<" => Conditionals::Less,
+                _   => return None,
+            };
+            Some((condition, Number::Integer(bound.parse().ok()?)))
+        },
+        [Token::Text(cmp), Token::Equals, Token::Number(bound)] => {
+            let condition = match &cmp[..] {
+                ">" => Conditionals::GreaterEq,
+                "<" => Conditionals::LessEq,
+                _   => return None,
+            };
+            Some((condition, Number::Integer(bound.parse().ok()?)))
+        },
+        _ => None,
+    }
+}
+
+// \R^n, \R^{3}
+fn parse_power(tokens: &[Token]) -> Option<Value> {
+    match tokens {
+        [Token::Number(power)] => Some(Value::Number(Number::Integer(power.parse().ok()?))),
+        [Token::Text(power)]   => Some(Value::Identifier(Identifier::TextIdent(power.clone()))),
+        [Token::GreekLetter(power)] => Some(Value::Identifier(Identifier::GreekLetter(power.clone()))),
+        _ => None,
+    }
+}
+
+// Attaches restrictions, powers and moduli to number sets, expects spaces to be removed
+fn make_number_sets(tokens: TokenString) -> TokenString {
+    let mut ret_vec = vec![];
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let set = tokens[i].clone();
+        i += 1;
+        if !is_number_set(&set) {
+            ret_vec.push(set);
+            continue;
         }
 
-        /**/ if token == Token::OpenCodeInline || token == Token::OpenCodeDisplay { in_extex_envmt = true; }
-        else if token == Token::CloseCodeInline || token == Token::CloseCodeDisplay { in_extex_envmt = false; }
-        else if token == Token::Percent { comment = true; }
-        else if token == Token::Newline { comment = false; }
+        let mut restriction = None;
+        let mut power = None;
+        loop {
+            let (decoration, offset) = match (tokens.get(i), tokens.get(i+1)) {
+                (Some(Token::Subscript), Some(Token::LeftBrace)) |
+                (Some(Token::Exponent),  Some(Token::LeftBrace)) => {
+                    let (offset, inner) = find_inner_string_req(&tokens, i+1);
+                    (inner, offset + 1)
+                },
+                (Some(Token::Subscript | Token::Exponent), Some(token)) => (vec![token.clone()], 2),
+                _ => break,
+            };
+            match &tokens[i] {
+                Token::Subscript => match parse_restriction(&decoration) {
+                    Some(parsed) => restriction = Some(parsed),
+                    None => break,
+                },
+                _ => match (parse_restriction(&decoration), parse_power(&decoration)) {
+                    (Some(parsed), _) => restriction = Some(parsed),
+                    (_, Some(parsed)) => power = Some(parsed),
+                    _ => break,
+                },
+            }
+            i += offset;
+        }
+        // \Z/n\Z
+        let mut modulus = None;
+        if let (Token::Integers, Some(Token::Operator(Operator::Div)), Some(n), Some(Token::Integers)) = (&set, tokens.get(i), tokens.get(i+1), tokens.get(i+2)) {
+            if let Some(parsed) = parse_power(std::slice::from_ref(n)) {
+                modulus = Some(parsed);
+                i += 3;
+            }
+        }
+
+        if restriction.is_none() && power.is_none() && modulus.is_none() {
+            ret_vec.push(set);
+        } else {
+            ret_vec.push(Token::NumberSet(Box::new(NumberSet { set: Box::new(set), restriction, power, modulus })));
+        }
+    }
+    ret_vec
+}
+
+// Comments are gone, pragmas are statements of their own
+fn remove_comments(tokens: TokenString) -> TokenString {
+    let mut ret = vec![];
+
+    for token in tokens.into_iter() {
+        match token {
+            Token::Comment(_) => (),
+            Token::Pragma(pragma) => ret.extend([Token::Comma, Token::Pragma(pragma), Token::Comma]),
+            token => ret.push(token),
+        }
     }
 
     ret
 }
 
+fn parse_code(tokens: TokenString) -> TokenString {
+    let tokens = remove_comments(tokens);
+    let tokens = remove_spacing(tokens);
+    let tokens = tokens.into_iter().map(parse_cmd_stub).collect();
+    let tokens = command_option_parser(tokens);
+    let tokens = tokens.into_iter().filter(|token| *token != Token::Space && *token != Token::Newline && !matches!(token, Token::NewCommand(_))).collect::<TokenString>();
+    make_number_sets(tokens)
+}
+
+// \, and \; in code are spacing, dropped with the rest of the whitespace. The document keeps them
+fn remove_spacing(tokens: TokenString) -> TokenString {
+    let mut ret_vec = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if token == Token::Backslash && matches!(tokens.peek(), Some(Token::Comma | Token::Semicolon)) {
+            tokens.next();
+            continue;
+        }
+        ret_vec.push(token);
+    }
+    ret_vec
+}
+
 fn parse_cmd_stub(token: Token) -> Token {
     match token {
         Token::CommandStub(cmd) => {
@@ -216,6 +639,8 @@ fn parse_cmd_stub(token: Token) -> Token {
                 "intprod"    => Token::BinOp(BinOps::InternalDirectProduct),
                 "cap"        => Token::BinOp(BinOps::Union),
                 "cup"        => Token::BinOp(BinOps::Intersection),
+                "bmod"       => Token::BinOp(BinOps::Modulo),
+                "mod"        => Token::BinOp(BinOps::Modulo),
 
                 "equiv"      => Token::Statement(Statements::Equivalent),
                 "sim"        => Token::Statement(Statements::Similar),
@@ -249,6 +674,28 @@ fn parse_cmd_stub(token: Token) -> Token {
                 "ifin"       => Token::Conditional(Conditionals::In),
                 "ifnin"      => Token::Conditional(Conditionals::NotIn),
 
+                // Number sets, \mathbb{R} is handled with the other commands
+
+                "Z"          => Token::Integers,
+                "R"          => Token::RealNumbers,
+                "Q"          => Token::RationalNumbers,
+                "C"          => Token::ComplexNumbers,
+                "N"          => Token::NatrualNumbers,
+
+                // Delimiters, | is worked out from where it is
+
+                "vert"       => Token::Pipe,
+                "lvert"      => Token::OpenDelimiter(UnOps::AbsoluteValue),
+                "rvert"      => Token::CloseDelimiter(UnOps::AbsoluteValue),
+                "lfloor"     => Token::OpenDelimiter(UnOps::Floor),
+                "rfloor"     => Token::CloseDelimiter(UnOps::Floor),
+                "lceil"      => Token::OpenDelimiter(UnOps::Ceiling),
+                "rceil"      => Token::CloseDelimiter(UnOps::Ceiling),
+
+                // Constants, \pi and e are in the interpreter's prelude so they can be shadowed
+
+                "infty"      => Token::Infinity,
+
                 // SYMBOLS
                 
                 "rightarrow" => Token::RightArrow,
@@ -259,6 +706,14 @@ fn parse_cmd_stub(token: Token) -> Token {
                 "then"       => Token::Then,
                 "else"       => Token::Else,
 
+                // Function annotations
+
+                "memo"       => Token::Memo,
+
+                // Operator names, \sin, \log, \gcd, ...
+
+                name if Builtin::from_name(name).is_some() => Token::Builtin(Builtin::from_name(name).unwrap()),
+
                 default      => Token::CommandStub(default.to_owned()),
             }
         }
@@ -266,9 +721,133 @@ fn parse_cmd_stub(token: Token) -> Token {
     }
 }
 
+// Commands that only change how math looks
+const TYPESETTING_COMMANDS: [&str; 11] = ["label", "tag", "nonumber", "notag", "quad", "qquad", "left", "right", "middle", "displaystyle", "textstyle"];
+
+// Math back into tokens for parse_code, without the parts that are only there for LaTeX
+fn math_tokens(nodes: &[Node], tokens: &mut TokenString) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => {
+                let mut text = text.iter();
+                while let Some(token) = text.next() {
+                    match token {
+                        // Spacing and other control symbols, \, \; \!
+                        Token::Backslash => { text.next(); },
+                        Token::Tab => (),
+                        // Alignment points, a &= b
+                        Token::Text(text) if text.contains('&') => {
+                            let text = text.replace('&', "");
+                            if !text.is_empty() {
+                                tokens.push(Token::Text(text));
+                            }
+                        },
+                        token => tokens.push(token.clone()),
+                    }
+                }
+            },
+            Node::Group(body) => {
+                tokens.push(Token::LeftBrace);
+                math_tokens(body, tokens);
+                tokens.push(Token::RightBrace);
+            },
+            Node::Command(command) if TYPESETTING_COMMANDS.contains(&&command.name[..]) || command.name.starts_with("big") || command.name.starts_with("Big") => (),
+            Node::Command(command) => {
+                tokens.push(Token::CommandStub(command.name.clone()));
+                if command.starred {
+                    tokens.push(Token::Operator(Operator::Mul));
+                }
+                for arg in &command.args {
+                    let (open, close, body) = match arg {
+                        Argument::Optional(body) => (Token::LeftBracket, Token::RightBracket, body),
+                        Argument::Required(body) => (Token::LeftBrace, Token::RightBrace, body),
+                    };
+                    tokens.push(open);
+                    math_tokens(body, tokens);
+                    tokens.push(close);
+                }
+            },
+            // \begin{aligned} inside of an equation
+            Node::Environment(environment) if matches!(environment.environment, Environment::Align | Environment::Gather) => math_tokens(&environment.body, tokens),
+            Node::Math(math)  => math_tokens(&math.body, tokens),
+            Node::Pragma(pragma) => tokens.push(Token::Pragma(pragma.clone())),
+            // Code blocks in math are their own code
+            Node::Code(_) | Node::Environment(_) | Node::Comment(_) | Node::Verbatim(_) | Node::NewCommand(_) => (),
+        }
+    }
+}
+
+// Every line of a formula is a statement
+pub(crate) fn parse_math(nodes: &[Node], macros: &Macros) -> TokenString {
+    let mut tokens = vec![];
+    math_tokens(nodes, &mut tokens);
+
+    let mut code = vec![Token::Comma];
+    for line in expand_macros(tokens, macros).split(|token| *token == Token::NewlineOperator) {
+        let line = line.iter().filter(|token| !matches!(token, Token::Space | Token::Newline)).cloned().collect::<TokenString>();
+        // a &= b \\ &= c, only the first line of a chain of equations can be code
+        if line.first() == Some(&Token::Equals) {
+            continue;
+        }
+        // Formulas in sentences end with punctuation
+        let line = match line.last() {
+            Some(Token::Period | Token::Comma) => &line[..(line.len() - 1)],
+            _ => &line[..],
+        };
+        code.extend(parse_code(line.to_vec()));
+        code.push(Token::Comma);
+    }
+    code
+}
+
+pub(crate) fn is_evaluated_environment(environment: &Environment) -> bool {
+    matches!(environment, Environment::Math | Environment::Equation | Environment::EqnArray | Environment::Align | Environment::Gather)
+}
+
+pub(crate) fn parse_code_block(block: &CodeBlock, macros: &Macros) -> TokenString {
+    parse_code(expand_macros(block.tokens.clone(), macros))
+}
+
+fn collect_code(nodes: &[Node], eval_math: bool, macros: &mut Macros, code: &mut TokenString) {
+    for node in nodes {
+        match node {
+            Node::NewCommand(new_command) => define_macro(macros, new_command),
+            Node::Pragma(pragma) => code.extend([Token::Comma, Token::Pragma(pragma.clone()), Token::Comma]),
+            // A block without a trailing comma doesn't run into the next one
+            Node::Code(block) => {
+                code.extend(parse_code_block(block, macros));
+                code.push(Token::Comma);
+            },
+            // Nested formulas were part of this one, aligned in an equation runs once
+            Node::Math(math) if eval_math => {
+                code.extend(parse_math(&math.body, macros));
+                continue;
+            },
+            Node::Environment(environment) if eval_math && is_evaluated_environment(&environment.environment) => {
+                code.extend(parse_math(&environment.body, macros));
+                continue;
+            },
+            _ => (),
+        }
+        for child in children(node) {
+            collect_code(child, eval_math, macros, code);
+        }
+    }
+}
+
+// Every code block in the document, ready for parse_to_ast
 pub fn parse(tokens: TokenString) -> TokenString {
-    let tokens = make_commands(tokens);
-    let tokens = command_option_parser(tokens);
-    let tokens = filter_what_gets_interpreted(tokens);
-    tokens.into_iter().filter(|token| *token != Token::Space && *token != Token::Newline && *token != Token::CloseCodeDisplay).collect::<TokenString>()
+    parse_with_math(tokens, false)
+}
+
+// With eval_math, or \jimtexeval in the document, $...$, \[...\], equation and align are code too
+pub fn parse_with_math(tokens: TokenString, eval_math: bool) -> TokenString {
+    let document = parse_document(tokens);
+    let eval_math = eval_math || document.evaluates_math();
+    let mut macros = Macros::new();
+    let mut code = vec![];
+
+    // Macros apply to the code after their definition
+    collect_code(&document.nodes, eval_math, &mut macros, &mut code);
+    code
 }
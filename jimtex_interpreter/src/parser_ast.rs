@@ -1,13 +1,75 @@
+use num::bigint::Sign;
+use num::BigRational;
+
 use crate::lexer::{Operator, Token, TokenString};
+use crate::parser::Command;
 use crate::ast::*;
 use crate::ast_types::*;
+use crate::format::{parse_format_option, FormatOption};
+use crate::errors::ParseError;
+use crate::real::{Real, Rounding};
 
 pub fn parse_to_ast(tokens: TokenString) -> Program {
     let mut program = vec![];
-    for slice in into_slices(tokens) {
+    let mut multi_letter = MultiLetter::Identifier;
+    for slice in into_statements(tokens) {
+        if let Err(error) = validate(&slice) {
+            program.push(Statement::Invalid(error));
+            continue;
+        }
+        if let [Token::Pragma(pragma)] = &slice[..] {
+            let pragma = parse_pragma(pragma);
+            if let Pragma::MultiLetter(policy) = &pragma {
+                multi_letter = policy.clone();
+            }
+            program.push(Statement::Pragma(pragma));
+            continue;
+        }
+        let split = |mut slice: TokenString| match (&multi_letter, slice.iter().position(|token| *token == Token::Equals)) {
+            // The head of a definition names what it defines, f(ab) = ab + 1 takes one argument ab
+            (MultiLetter::Product, Some(equals)) => {
+                let body = slice.split_off(equals + 1);
+                let arguments = match slice.iter().position(|token| *token == Token::LeftParen) {
+                    Some(open) => slice[open..].iter().filter_map(|token| match token {
+                        Token::Text(argument) => Some(argument.clone()),
+                        _ => None,
+                    }).collect(),
+                    None => vec![],
+                };
+                slice.extend(split_letters(body, &arguments));
+                slice
+            },
+            (MultiLetter::Product, None) => split_letters(slice, &[]),
+            (MultiLetter::Identifier, _) => slice,
+        };
+        // \jimtexformat{scientific 3, thousands comma}{x}
+        if let [Token::Command(command)] = &slice[..] {
+            if let ("jimtexformat", [options, expression]) = (&command.name[..], &command.req[..]) {
+                let expression = expression.iter().filter(|token| !matches!(token, Token::Space | Token::Newline)).cloned().collect();
+                program.push(match parse_format_options(options) {
+                    Ok(options) => Statement::FormattedExpression(parse_expression(split(expression)), options),
+                    Err(error)  => Statement::Invalid(error),
+                });
+                continue;
+            }
+        }
+        let slice = split(slice);
+        // \memo f(n) = ... with no declaration still needs one to carry the annotation
+        if let (Some(Token::Memo), SliceType::FunctionDefinition) = (slice.first(), slice_type(&slice)) {
+            let definition = parse_function_def(slice[1..].to_vec());
+            program.push(Statement::Declaration(Declaration::FunctionDeclaration(Box::new(FunctionDeclaration {
+                identifier: definition.identifier.clone(),
+                domain:     Token::Any,
+                codomain:   Token::Any,
+                memoized:   true,
+            }))));
+            program.push(Statement::FunctionDefinition(definition));
+            continue;
+        }
         match slice_type(&slice) {
            SliceType::Declaration        => program.push(Statement::Declaration(parse_declaration(slice))),
            SliceType::FunctionDefinition => program.push(Statement::FunctionDefinition(parse_function_def(slice))),
+           SliceType::SequenceDefinition => program.push(Statement::SequenceDefinition(parse_sequence_def(slice))),
            SliceType::Expression         => program.push(Statement::Expression(parse_expression(slice))),
         }
     }
@@ -15,10 +77,85 @@ pub fn parse_to_ast(tokens: TokenString) -> Program {
     Program { program }
 }
 
+// What the rest of the parser would trip over, found before it runs
+fn validate(tokens: &[Token]) -> Result<(), ParseError> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::ParseError(error) => return Err(error.clone()),
+            Token::LeftParen | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace => depth = depth.checked_sub(1).ok_or(ParseError::UnbalancedBrackets)?,
+            // \{1, 2\} is only ever the elements of a group, (\{1, 2\}, +)
+            Token::EscapedLeftBrace => {
+                let close = escaped_brace_close(tokens, i);
+                let carrier = i > 0 && tokens[i - 1] == Token::LeftParen
+                    && close.is_some_and(|close| tokens.get(close + 1) == Some(&Token::Comma));
+                if !carrier {
+                    return Err(ParseError::BareSet);
+                }
+            },
+            _ => (),
+        }
+    }
+    match depth {
+        0 => Ok(()),
+        _ => Err(ParseError::UnbalancedBrackets),
+    }
+}
+
+fn escaped_brace_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::EscapedLeftBrace  => depth += 1,
+            Token::EscapedRightBrace => depth -= 1,
+            _ => continue,
+        }
+        if depth == 0 {
+            return Some(i);
+        }
+    }
+    None
+}
+
+// Anything misspelled or out of range is kept as Unknown, it's ignored with a warning
+fn parse_pragma(pragma: &str) -> Pragma {
+    let parsed = match pragma.split_whitespace().collect::<Vec<_>>()[..] {
+        ["precision", digits] => digits.parse().ok().map(Pragma::Precision),
+        ["strict"] | ["strict", "true"] => Some(Pragma::Strict(true)),
+        ["strict", "false"] => Some(Pragma::Strict(false)),
+        ["exact"] | ["exact", "true"] => Some(Pragma::Exact(true)),
+        ["exact", "false"] => Some(Pragma::Exact(false)),
+        ["symbolic"] | ["symbolic", "true"] => Some(Pragma::Symbolic(true)),
+        ["symbolic", "false"] => Some(Pragma::Symbolic(false)),
+        // %jimtex: format fixed 2, %jimtex: fractions mixed, %jimtex: thousands comma
+        ["format", ref option @ ..] | ["fractions", ref option @ ..] => parse_format_option(&option.join(" ")).map(Pragma::Format),
+        ["thousands", _] => parse_format_option(pragma).map(Pragma::Format),
+        ["digits", digits] => digits.parse().ok().filter(|digits| *digits > 0).map(Pragma::Digits),
+        ["rounding", rounding] => Rounding::from_name(rounding).map(Pragma::Rounding),
+        ["multiletter", "identifier"] => Some(Pragma::MultiLetter(MultiLetter::Identifier)),
+        ["multiletter", "product"]    => Some(Pragma::MultiLetter(MultiLetter::Product)),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Pragma::Unknown(pragma.trim().to_owned()))
+}
+
+fn parse_format_options(options: &[Token]) -> Result<Vec<FormatOption>, ParseError> {
+    options.split(|token| *token == Token::Comma).map(|option| {
+        let option = option.iter().filter_map(|token| match token {
+            Token::Text(word) | Token::Number(word) => Some(word.clone()),
+            _ => None,
+        }).collect::<Vec<_>>().join(" ");
+        parse_format_option(&option).ok_or(ParseError::UnknownFormat(option))
+    }).collect()
+}
+
 fn slice_type(tokens: &TokenString) -> SliceType {
-    if let Some((left, _)) = tokens.split_once(|token| *token == Token::Equals) {
+    if let Some((left, right)) = tokens.split_once(|token| *token == Token::Equals) {
         if left.contains(&Token::LeftParen) {
             SliceType::FunctionDefinition
+        } else if sequence_index(left, right).is_some() {
+            SliceType::SequenceDefinition
         } else {
             SliceType::Declaration
         }
@@ -32,9 +169,23 @@ fn slice_type(tokens: &TokenString) -> SliceType {
 enum SliceType {
     Declaration,
     FunctionDefinition,
+    SequenceDefinition,
     Expression,
 }
 
+// a_n = ... defines a sequence when the right hand side depends on n, otherwise a_n is a variable
+fn sequence_index(left: &[Token], right: &[Token]) -> Option<Token> {
+    let index = match left {
+        [Token::Text(_) | Token::GreekLetter(_), Token::Subscript, index] => index,
+        [Token::Text(_) | Token::GreekLetter(_), Token::Subscript, Token::LeftBrace, index, Token::RightBrace] => index,
+        _ => return None,
+    };
+    match index {
+        Token::Text(_) | Token::GreekLetter(_) if right.contains(index) => Some(index.clone()),
+        _ => None,
+    }
+}
+
 fn parse_expression(tokens: TokenString) -> Expression {
     parse_conditionals(tokens)
 }
@@ -62,6 +213,8 @@ enum Precedence {
     Three,
     Two,
     One,
+    // Exponents bind tightest
+    Zero,
     None
 }
 
@@ -78,7 +231,7 @@ fn precedence(token: Option<&Token>) -> Precedence {
                 Operator::Sub => Precedence::Three,
             }
         },
-        Token::Exponent => Precedence::Three,
+        Token::Exponent => Precedence::Zero,
         Token::BinOp(binop) => {
             match binop {
                 BinOps::Multiply |
@@ -88,12 +241,15 @@ fn precedence(token: Option<&Token>) -> Precedence {
                 BinOps::ExternalDirectProduct |
                 BinOps::InternalDirectProduct |
                 BinOps::Subtraction | 
+                BinOps::Binomial |
+                BinOps::Modulo |
                 BinOps::Addition => Precedence::Two,
                 BinOps::Union  |
                 BinOps::Intersection |
                 BinOps::BoolOr |
                 BinOps::PlusMinus |
                 BinOps::SetDifference => Precedence::One,
+                BinOps::Power => Precedence::Zero,
             }
         },
         Token::UnOps(_) => Precedence::Three,
@@ -101,6 +257,164 @@ fn precedence(token: Option<&Token>) -> Precedence {
     }
 }
 
+// Operators on the stack that bind at least as tightly come off first, but never past a parenthesis
+fn pops_before(token: &Token, top: Option<&Token>) -> bool {
+    match top {
+        None | Some(Token::LeftParen) => false,
+        top => precedence(Some(token)) < precedence(top)
+            || precedence(Some(token)) == precedence(top) && associativity(token) == Associativity::Left,
+    }
+}
+
+fn ends_operand(token: Option<&Token>) -> bool {
+    matches!(token, Some(
+        Token::Real(_) | Token::Infinity | Token::Number(_) | Token::Text(_) | Token::GreekLetter(_) | Token::Identifier(_)
+        | Token::FunctionCall(_) | Token::BuiltinCall(_) | Token::SequenceTerm(_) | Token::Limit(_) | Token::Matrix(_) | Token::UnaryOperation(_) | Token::BinaryOperation(_)
+        | Token::RightParen | Token::RightBrace | Token::CloseDelimiter(_) | Token::Exclamation
+    ))
+}
+
+fn starts_operand(token: &Token) -> bool {
+    matches!(token,
+        Token::Real(_) | Token::Infinity | Token::Number(_) | Token::Text(_) | Token::GreekLetter(_) | Token::Identifier(_)
+        | Token::FunctionCall(_) | Token::BuiltinCall(_) | Token::SequenceTerm(_) | Token::Limit(_) | Token::Matrix(_) | Token::UnaryOperation(_) | Token::BinaryOperation(_)
+        | Token::LeftParen | Token::LeftBrace
+    )
+}
+
+// Arguments of a command keep their whitespace
+fn parse_argument(argument: &[Token]) -> Value {
+    parse_value(argument.iter().filter(|token| !matches!(token, Token::Space | Token::Newline)).cloned().collect())
+}
+
+// Commands with arguments that are operands, \binom{n}{k}, \frac{a}{b}, \sqrt{x}
+fn make_commands(tokens: TokenString) -> TokenString {
+    tokens.into_iter().map(|token| match token {
+        Token::Command(command) => match (&command.name[..], &command.req[..]) {
+            ("frac" | "tfrac" | "dfrac", [a, b]) => Token::BinaryOperation(Box::new(BinaryOperation {
+                value_1: parse_argument(a),
+                binop:   BinOps::Divide,
+                value_2: parse_argument(b),
+            })),
+            ("sqrt", [x]) => Token::BinaryOperation(Box::new(BinaryOperation {
+                value_1: parse_argument(x),
+                binop:   BinOps::Power,
+                value_2: Value::Number(Number::Rational(BigRational::new(1.into(), 2.into()))),
+            })),
+            ("binom" | "tbinom" | "dbinom", [n, k]) => Token::BinaryOperation(Box::new(BinaryOperation {
+                value_1: parse_argument(n),
+                binop:   BinOps::Binomial,
+                value_2: parse_argument(k),
+            })),
+            _ => Token::Command(command),
+        },
+        token => token,
+    }).collect()
+}
+
+// |x|, \lvert x \rvert, \lfloor x \rfloor and \lceil x \rceil
+fn make_delimited_operations(tokens: TokenString) -> TokenString {
+    // A | only closes an open | when it comes right after an operand, 2|x| opens and |x| |y| is a product
+    let mut resolved: Vec<Token> = vec![];
+    let mut open = 0;
+    for token in tokens {
+        match token {
+            Token::Pipe if open > 0 && ends_operand(resolved.last()) => {
+                open -= 1;
+                resolved.push(Token::CloseDelimiter(UnOps::AbsoluteValue));
+            },
+            Token::Pipe => {
+                open += 1;
+                resolved.push(Token::OpenDelimiter(UnOps::AbsoluteValue));
+            },
+            token => resolved.push(token),
+        }
+    }
+
+    let mut res: Vec<Token> = vec![];
+    let mut i = 0;
+    while i < resolved.len() {
+        let unop = match &resolved[i] {
+            Token::OpenDelimiter(unop) => unop,
+            Token::CloseDelimiter(_) => {
+                res.push(Token::ParseError(ParseError::UnbalancedDelimiter));
+                i += 1;
+                continue;
+            },
+            token => {
+                res.push(token.clone());
+                i += 1;
+                continue;
+            },
+        };
+        let mut depth = 1;
+        let mut end = i;
+        while depth > 0 {
+            end += 1;
+            match resolved.get(end) {
+                Some(Token::OpenDelimiter(_))  => depth += 1,
+                Some(Token::CloseDelimiter(_)) => depth -= 1,
+                None => {
+                    res.push(Token::ParseError(ParseError::UnbalancedDelimiter));
+                    return res;
+                },
+                _ => (),
+            }
+        }
+        res.push(Token::UnaryOperation(Box::new(UnaryOperation { unop: unop.clone(), value: parse_value(resolved[(i+1)..end].to_vec()) })));
+        i = end + 1;
+    }
+
+    res
+}
+
+// Juxtaposition is multiplication, 2x, 3(n+1), (a+b)(a-b)
+fn make_implicit_multiplication(tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+
+    for token in tokens {
+        if ends_operand(res.last()) && starts_operand(&token) {
+            res.push(Token::Operator(Operator::Mul));
+        }
+        res.push(token);
+    }
+
+    res
+}
+
+// With %jimtex: multiletter product, ab is a * b. Function names, subscripts, the arguments of the
+// function being defined and the dx closing an integral stay whole
+fn split_letters(tokens: TokenString, arguments: &[String]) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let mut subscript_depth = 0;
+    let mut integrals = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LeftBrace if subscript_depth > 0 || res.last() == Some(&Token::Subscript) => subscript_depth += 1,
+            Token::RightBrace if subscript_depth > 0 => subscript_depth -= 1,
+            Token::Command(command) if command.name == "int" => integrals += 1,
+            Token::Text(dx) if integrals > 0 && differential(std::slice::from_ref(token)).is_some() => {
+                integrals -= 1;
+                res.push(Token::Text(dx.clone()));
+                continue;
+            },
+            Token::Text(text) if subscript_depth == 0
+                && res.last() != Some(&Token::Subscript)
+                && !arguments.contains(text)
+                && !matches!(tokens.get(i+1), Some(Token::LeftParen | Token::Colon))
+                && text.chars().all(|char| char.is_alphabetic()) => {
+                res.extend(text.chars().map(|letter| Token::Text(letter.to_string())));
+                continue;
+            },
+            _ => (),
+        }
+        res.push(token.clone());
+    }
+
+    res
+}
+
 fn make_function_calls(tokens: TokenString) -> TokenString {
     let mut res: Vec<Token> = vec![];
     let copy = tokens.clone();
@@ -113,6 +427,7 @@ fn make_function_calls(tokens: TokenString) -> TokenString {
         }
         match token {
             Token::GreekLetter(_)
+            | Token::Identifier(_)
             | Token::Text(_) => {
                 if let Some(Token::LeftParen) = copy.get(i+1) {
                     let mut balanced = 1;
@@ -125,8 +440,12 @@ fn make_function_calls(tokens: TokenString) -> TokenString {
                         count += 1;
                     }
 
+                    let function = match token {
+                        Token::Identifier(ident) => ident,
+                        token => parse_identifier(vec![token]),
+                    };
                     res.push(Token::FunctionCall(FunctionCall { 
-                        function: parse_identifier(vec![token]), 
+                        function, 
                         args: copy.clone()[(i+2)..=(i+count-1)]
                             .split(|token| *token == Token::Comma)
                             .map(|slice| parse_value(slice.to_vec()))
@@ -144,6 +463,509 @@ fn make_function_calls(tokens: TokenString) -> TokenString {
     res
 }
 
+// Index of the bracket closing the one at open
+fn closing_bracket(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LeftParen | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return i;
+        }
+    }
+    unreachable!("validate checks that the brackets are balanced")
+}
+
+// Arguments split on the commas that aren't inside another bracket
+fn split_arguments(tokens: &[Token]) -> Vec<Value> {
+    split_commas(tokens).into_iter().map(|argument| parse_value(argument.to_vec())).collect()
+}
+
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LeftParen | Token::LeftBrace | Token::EscapedLeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace | Token::EscapedRightBrace => depth -= 1,
+            Token::Command(command) if command.name == "langle" => depth += 1,
+            Token::Command(command) if command.name == "rangle" => depth -= 1,
+            Token::Comma if depth == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+// (1, 2), or a finite group or ring when the first entry is a set, (\Z/6\Z, +), (\{1, 2, 3, 4\}, m)
+// and (\Z/6\Z, +, \cdot)
+fn parse_tuple(tokens: &[Token]) -> Option<Value> {
+    if tokens.first() != Some(&Token::LeftParen) || closing_bracket(tokens, 0) != tokens.len() - 1 {
+        return None;
+    }
+    let parts = split_commas(&tokens[1..(tokens.len()-1)]);
+    if parts.len() < 2 {
+        return None;
+    }
+    let carrier = match parts[0] {
+        [Token::EscapedLeftBrace, elements @ .., Token::EscapedRightBrace] => Carrier::Elements(split_arguments(elements)),
+        [set @ Token::NumberSet(_)] => Carrier::Set(set.clone()),
+        _ => {
+            let values = parts.into_iter().map(|part| parse_value(part.to_vec())).collect();
+            return Some(Value::Expression(Box::new(Expression::Tuple(values))));
+        },
+    };
+    let operations = parts[1..].iter().map(|operation| match operation {
+        [Token::Operator(operator)] => Operation::BinOp(match operator {
+            Operator::Add => BinOps::Addition,
+            Operator::Sub => BinOps::Subtraction,
+            Operator::Mul => BinOps::Multiply,
+            Operator::Div => BinOps::Divide,
+        }),
+        [Token::BinOp(binop)] => Operation::BinOp(binop.clone()),
+        operation => Operation::Value(parse_value(operation.to_vec())),
+    }).collect();
+    Some(Value::Expression(Box::new(Expression::Structure(StructureLiteral { carrier, operations }))))
+}
+
+// \langle a, b \rangle_G, the subgroup of G they generate
+fn make_generated_subgroups(tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if !matches!(&tokens[i], Token::Command(command) if command.name == "langle") {
+            res.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+        let mut depth = 0;
+        let close = (i..tokens.len()).find(|j| {
+            match &tokens[*j] {
+                Token::Command(command) if command.name == "langle" => depth += 1,
+                Token::Command(command) if command.name == "rangle" => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        });
+        let Some(close) = close else {
+            res.push(Token::ParseError(ParseError::UnbalancedDelimiter));
+            return res;
+        };
+        let mut args = split_arguments(&tokens[(i+1)..close]);
+        i = match tokens.get(close + 1..close + 3) {
+            Some([Token::Subscript, Token::LeftBrace]) => {
+                let end = closing_bracket(&tokens, close + 2);
+                args.push(parse_value(tokens[(close+3)..end].to_vec()));
+                end + 1
+            },
+            Some([Token::Subscript, group]) => {
+                args.push(parse_value(vec![group.clone()]));
+                close + 3
+            },
+            _ => {
+                res.push(Token::ParseError(ParseError::GeneratedWithoutGroup));
+                return res;
+            },
+        };
+        res.push(Token::BuiltinCall(BuiltinCall { function: Builtin::Generated, args }));
+    }
+
+    res
+}
+
+// \frac{d}{dx}, \frac{\partial}{\partial y}, \frac{\mathrm{d}^2}{\mathrm{d}x^2}, the variable and how many times
+fn derivative(command: &Command) -> Option<(Identifier, usize)> {
+    let ("frac", [numerator, denominator]) = (&command.name[..], &command.req[..]) else {
+        return None;
+    };
+    let strip = |tokens: &TokenString| tokens.iter().filter(|token| **token != Token::Space).cloned().collect::<TokenString>();
+    let order = |tokens: &[Token]| match tokens {
+        [] => Some(1),
+        [Token::Exponent, Token::Number(n)] | [Token::Exponent, Token::LeftBrace, Token::Number(n), Token::RightBrace] => n.parse().ok(),
+        _ => None,
+    };
+    let (numerator, denominator) = (strip(numerator), strip(denominator));
+    let [d, numerator_order @ ..] = &numerator[..] else {
+        return None;
+    };
+    let n = order(numerator_order).filter(|_| is_d(d))?;
+    let (variable, length) = differential(&denominator)?;
+    (order(&denominator[length..])? == n).then_some((variable, n))
+}
+
+// d, \partial or \mathrm{d}
+fn is_d(token: &Token) -> bool {
+    match token {
+        Token::Text(d) => d == "d",
+        Token::Command(command) => command.name == "partial" || command.name == "mathrm" && command.req == [vec![Token::Text("d".to_owned())]],
+        _ => false,
+    }
+}
+
+// dx, d\theta, \mathrm{d}x at the start of the tokens, the variable and how many tokens it takes
+fn differential(tokens: &[Token]) -> Option<(Identifier, usize)> {
+    match tokens {
+        // dx is one piece of text
+        [Token::Text(dx), ..] if dx.len() > 1 && dx.starts_with('d') => Some((Identifier::TextIdent(dx[1..].to_owned()), 1)),
+        [d, variable @ (Token::Text(_) | Token::GreekLetter(_)), ..] if is_d(d) => Some((text_or_greek_to_ident(variable.clone()), 2)),
+        _ => None,
+    }
+}
+
+// \int_a^b f(x) \, dx and \int f(x) \, dx. The integrand runs up to the differential, nested
+// integrals each close with their own
+fn make_integrals(mut tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if !matches!(&tokens[i], Token::Command(command) if command.name == "int") {
+            res.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+        i += 1;
+
+        let (mut lower, mut upper) = (None, None);
+        while let Some(limit @ (Token::Subscript | Token::Exponent)) = tokens.get(i).cloned() {
+            let bound = match tokens.get(i+1) {
+                Some(Token::LeftBrace) => {
+                    let end = closing_bracket(&tokens, i+1);
+                    let bound = parse_value(tokens[(i+1)..=end].to_vec());
+                    i = end + 1;
+                    bound
+                },
+                // \int_0^12 is \int_0^1 2
+                Some(Token::Number(number)) if number.len() > 1 => {
+                    let bound = parse_value(vec![Token::Number(number[..1].to_owned())]);
+                    tokens[i+1] = Token::Number(number[1..].to_owned());
+                    i += 1;
+                    bound
+                },
+                Some(token) => {
+                    let bound = parse_value(vec![token.clone()]);
+                    i += 2;
+                    bound
+                },
+                None => {
+                    res.push(Token::ParseError(ParseError::IntegralWithoutBound));
+                    return res;
+                },
+            };
+            match limit {
+                Token::Subscript => lower = Some(bound),
+                _                => upper = Some(bound),
+            }
+        }
+
+        let start = i;
+        let mut open = 0;
+        let (variable, length) = loop {
+            match (tokens.get(i), differential(&tokens[i..])) {
+                (None, _) => {
+                    res.push(Token::ParseError(ParseError::IntegralWithoutDifferential));
+                    return res;
+                },
+                (_, Some(differential)) if open == 0 => break differential,
+                (_, Some((_, length))) => {
+                    open -= 1;
+                    i += length;
+                },
+                (Some(Token::Command(command)), _) if command.name == "int" => {
+                    open += 1;
+                    i += 1;
+                },
+                _ => i += 1,
+            }
+        };
+        let mut args = vec![parse_value(tokens[start..i].to_vec()), Value::Identifier(variable)];
+        i += length;
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => args.extend([lower, upper]),
+            (None, None) => (),
+            _ => {
+                res.push(Token::ParseError(ParseError::IntegralWithOneBound));
+                return res;
+            },
+        }
+        res.push(Token::BuiltinCall(BuiltinCall { function: Builtin::Integral, args }));
+    }
+
+    res
+}
+
+// Where an argument without brackets ends, at the next operator outside of any brackets.
+// At the next builtin too for builtins, \sin x \cos x
+fn operand_end(tokens: &[Token], start: usize, stop_at_builtins: bool) -> usize {
+    let mut i = start;
+    let mut depth = 0;
+    let mut pipes = 0;
+    while let Some(token) = tokens.get(i) {
+        let outside = depth == 0 && pipes == 0;
+        match token {
+            Token::LeftParen | Token::LeftBrace | Token::OpenDelimiter(_) => depth += 1,
+            Token::RightParen | Token::RightBrace | Token::CloseDelimiter(_) if depth == 0 => break,
+            Token::RightParen | Token::RightBrace | Token::CloseDelimiter(_) => depth -= 1,
+            Token::Pipe if pipes > 0 && ends_operand(tokens.get(i-1)) => pipes -= 1,
+            // The | closing an absolute value around the call
+            Token::Pipe if outside && i > start && ends_operand(tokens.get(i-1)) => break,
+            Token::Pipe => pipes += 1,
+            Token::Operator(_) | Token::BinOp(_) | Token::Conditional(_)
+            | Token::Comma | Token::Equals | Token::Then | Token::Else if outside => break,
+            Token::Builtin(_) if stop_at_builtins && outside && i > start => break,
+            _ => (),
+        }
+        i += 1;
+    }
+    i
+}
+
+// \lim_{x \to a} f, \lim_{x \to 0^+} f, \lim_{x \to -\infty} f. Everything up to the next + or - is
+// the expression, so \lim_{x \to \infty} (1 + \frac{1}{x})^x takes the power with it
+fn make_limits(tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if !matches!(&tokens[i], Token::Command(command) if command.name == "lim") {
+            res.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+        let (Some(Token::Subscript), Some(Token::LeftBrace)) = (tokens.get(i+1), tokens.get(i+2)) else {
+            res.push(Token::ParseError(ParseError::LimitWithoutSubscript));
+            return res;
+        };
+        let end = closing_bracket(&tokens, i+2);
+        let subscript = &tokens[(i+3)..end];
+        let Some((variable, approaches)) = subscript.split_once(|token| *token == Token::RightArrow || matches!(token, Token::Command(command) if command.name == "to")) else {
+            res.push(Token::ParseError(ParseError::LimitWithoutApproach));
+            return res;
+        };
+        let (approaches, side) = match approaches {
+            [rest @ .., Token::Exponent, Token::Operator(sign)]
+            | [rest @ .., Token::Exponent, Token::LeftBrace, Token::Operator(sign), Token::RightBrace] => {
+                (rest, if *sign == Operator::Sub { Side::Left } else { Side::Right })
+            },
+            approaches => (approaches, Side::Both),
+        };
+        let approaches = match approaches {
+            [Token::Operator(Operator::Sub), Token::Infinity] => Value::Number(Number::Infinity(Sign::Minus)),
+            [Token::Operator(Operator::Add), Token::Infinity] => Value::Number(Number::Infinity(Sign::Plus)),
+            approaches => parse_value(approaches.to_vec()),
+        };
+
+        let start = end + 1;
+        i = operand_end(&tokens, start, false);
+        res.push(Token::Limit(Limit {
+            variable: parse_identifier(variable.to_vec()),
+            approaches,
+            side,
+            value: parse_value(tokens[start..i].to_vec()),
+        }));
+    }
+
+    res
+}
+
+// a \equiv b \pmod{n}, a \cong b \pmod n, a \ifcong b (\bmod n). The relation binds loosest, so
+// everything on either side is an operand
+fn make_congruences(tokens: TokenString) -> TokenString {
+    let mut depth = 0;
+    let relation = tokens.iter().position(|token| {
+        match token {
+            Token::LeftParen | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace => depth -= 1,
+            _ => (),
+        }
+        depth == 0 && matches!(token, Token::Statement(Statements::Equivalent | Statements::Congruent) | Token::Conditional(Conditionals::Congruent))
+    });
+    let Some(relation) = relation else {
+        return tokens;
+    };
+    let (right, modulus) = split_modulus(&tokens[(relation+1)..]);
+    let mut args = vec![parse_value(tokens[..relation].to_vec()), parse_value(right.to_vec())];
+    args.extend(modulus.map(|modulus| parse_value(modulus.to_vec())));
+    vec![Token::BuiltinCall(BuiltinCall { function: Builtin::Congruence, args })]
+}
+
+fn split_modulus(tokens: &[Token]) -> (&[Token], Option<&[Token]>) {
+    let pmod = tokens.iter().enumerate().find_map(|(i, token)| match token {
+        Token::Command(command) if command.name == "pmod" => Some((i, command)),
+        _ => None,
+    });
+    if let Some((i, command)) = pmod {
+        return match command.req.first() {
+            Some(modulus) => (&tokens[..i], Some(modulus)),
+            None => (&tokens[..i], Some(&tokens[(i+1)..])),
+        };
+    }
+    match tokens {
+        [.., Token::RightParen] => match tokens.windows(2).rposition(|pair| pair == [Token::LeftParen, Token::BinOp(BinOps::Modulo)]) {
+            Some(i) => (&tokens[..i], Some(&tokens[(i+2)..(tokens.len()-1)])),
+            None => (tokens, None),
+        },
+        _ => (tokens, None),
+    }
+}
+
+// \gcd(a, b), \log_2 8, \sin 2x. Without brackets the argument runs up to the next operator,
+// so \sin 2x + 1 is \sin(2x) + 1 and \sin x \cos x is a product. \frac{d}{dx} works the same way
+// with its variable where a base would go
+fn make_builtin_calls(mut tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let (function, mut base, order) = match &tokens[i] {
+            Token::Builtin(function) => (function.clone(), None, 1),
+            Token::Command(command) if derivative(command).is_some() => {
+                let (variable, order) = derivative(command).unwrap();
+                (Builtin::Derivative, Some(Value::Identifier(variable)), order)
+            },
+            token => {
+                res.push(token.clone());
+                i += 1;
+                continue;
+            },
+        };
+        i += 1;
+
+        if tokens.get(i) == Some(&Token::Subscript) {
+            match tokens.get(i+1) {
+                Some(Token::LeftBrace) => {
+                    let end = closing_bracket(&tokens, i+1);
+                    base = Some(parse_value(tokens[(i+1)..=end].to_vec()));
+                    i = end + 1;
+                },
+                // \log_28 is \log_2 8
+                Some(Token::Number(number)) if number.len() > 1 => {
+                    base = Some(parse_value(vec![Token::Number(number[..1].to_owned())]));
+                    tokens[i+1] = Token::Number(number[1..].to_owned());
+                    i += 1;
+                },
+                Some(token) => {
+                    base = Some(parse_value(vec![token.clone()]));
+                    i += 2;
+                },
+                None => {
+                    res.push(Token::ParseError(ParseError::MissingSubscript));
+                    break;
+                },
+            }
+        }
+
+        let mut args = match tokens.get(i) {
+            Some(Token::LeftParen | Token::LeftBrace) => {
+                let end = closing_bracket(&tokens, i);
+                let args = split_arguments(&tokens[(i+1)..end]);
+                i = end + 1;
+                args
+            },
+            _ => {
+                let start = i;
+                i = operand_end(&tokens, start, true);
+                vec![parse_value(tokens[start..i].to_vec())]
+            },
+        };
+        let variable = base.clone();
+        args.extend(base);
+        let mut call = BuiltinCall { function, args };
+        // \frac{d^2}{dx^2} is \frac{d}{dx} twice
+        for _ in 1..order {
+            call = BuiltinCall { function: Builtin::Derivative, args: vec![Value::Expression(Box::new(Expression::BuiltinCall(call))), variable.clone().unwrap()] };
+        }
+        res.push(Token::BuiltinCall(call));
+    }
+
+    res
+}
+
+fn make_subscripts(tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let copy = tokens.clone();
+    let mut ignore = 0;
+
+    for (i, token) in tokens.into_iter().enumerate() {
+        if ignore > 0 {
+            ignore -= 1;
+            continue;
+        }
+        match token {
+            Token::GreekLetter(_)
+            | Token::Text(_) if copy.get(i+1) == Some(&Token::Subscript) => {
+                let sequence = text_or_greek_to_ident(token);
+                // Whatever is left of an unbraced number, a_10 is a_1 followed by 0
+                let mut trailing = None;
+                let index: TokenString = match copy.get(i+2) {
+                    Some(Token::LeftBrace) => {
+                        let mut balanced = 1;
+                        let mut end = i + 2;
+
+                        while balanced > 0 {
+                            end += 1;
+                            match copy.get(end) {
+                                Some(Token::LeftBrace)  => balanced += 1,
+                                Some(Token::RightBrace) => balanced -= 1,
+                                None => panic!("Unbalanced subscript"),
+                                _ => (),
+                            }
+                        }
+                        ignore = end - i;
+                        copy[(i+3)..end].to_vec()
+                    },
+                    Some(Token::Number(number)) if number.len() > 1 => {
+                        ignore = 2;
+                        trailing = Some(Token::Number(number[1..].to_owned()));
+                        vec![Token::Number(number[..1].to_owned())]
+                    },
+                    Some(index) => {
+                        ignore = 2;
+                        vec![index.clone()]
+                    },
+                    None => {
+                        res.push(Token::ParseError(ParseError::MissingSubscript));
+                        break;
+                    },
+                };
+
+                if is_identifier(&index) {
+                    res.push(Token::Identifier(Identifier::SubScriptIdent(Box::new(SubScriptIdent {
+                        first_ident: sequence,
+                        secnd_ident: parse_identifier(index),
+                    }))));
+                } else {
+                    res.push(Token::SequenceTerm(SequenceTerm { sequence, index: parse_value(index) }));
+                }
+                res.extend(trailing);
+            },
+            token => res.push(token),
+        }
+    }
+
+    res
+}
+
+// x_1, x_{10}, x_{ij} and x_{i_1} subscripts are identifiers, x_{i+1} is not
+fn is_identifier(tokens: &[Token]) -> bool {
+    match tokens {
+        [Token::Number(_)] => true,
+        [Token::Text(_) | Token::GreekLetter(_)] => true,
+        [Token::Text(_) | Token::GreekLetter(_), Token::Subscript, Token::LeftBrace, index @ .., Token::RightBrace] => is_identifier(index),
+        [Token::Text(_) | Token::GreekLetter(_), Token::Subscript, index @ ..] => is_identifier(index),
+        _ => false,
+    }
+}
+
 fn parse_value(tokens: TokenString) -> Value {
     if let Some(Token::If) = tokens.first() {
         if let [condition, true_exp, false_exp] = tokens
@@ -158,9 +980,27 @@ fn parse_value(tokens: TokenString) -> Value {
             panic!("should split into three")
         }
     } else {
+        if let Some(value) = parse_tuple(&tokens) {
+            return value;
+        }
         let mut res: TokenString = vec![];
+        let tokens = make_congruences(tokens);
+        let tokens = make_negative_numbers(tokens);
+        let tokens = make_matrices(tokens);
+        let tokens = make_generated_subgroups(tokens);
+        let tokens = make_limits(tokens);
+        let tokens = make_integrals(tokens);
         let tokens = make_real_numbers(tokens);
+        let tokens = make_builtin_calls(tokens);
+        let tokens = make_subscripts(tokens);
         let tokens = make_function_calls(tokens);
+        let tokens = make_commands(tokens);
+        let tokens = make_delimited_operations(tokens);
+        let tokens = make_implicit_multiplication(tokens);
+        // Left by one of the passes, the value fails when it's evaluated
+        if let Some(Token::ParseError(error)) = tokens.iter().find(|token| matches!(token, Token::ParseError(_))) {
+            return Value::Expression(Box::new(Expression::Invalid(error.clone())));
+        }
         // Base cases of number and identifier
         let mut stack: TokenString = vec![];
 
@@ -169,6 +1009,9 @@ fn parse_value(tokens: TokenString) -> Value {
                 Token::Real(number) => {
                     res.push(Token::Real(number));
                 },
+                Token::Infinity => {
+                    res.push(Token::Infinity);
+                },
                 Token::Number(number) => {
                     res.push(Token::Number(number.parse().unwrap()));
                 },
@@ -181,38 +1024,56 @@ fn parse_value(tokens: TokenString) -> Value {
                 Token::FunctionCall(call) => {
                     res.push(Token::FunctionCall(call));
                 },
+                Token::BuiltinCall(call) => {
+                    res.push(Token::BuiltinCall(call));
+                },
+                Token::SequenceTerm(term) => {
+                    res.push(Token::SequenceTerm(term));
+                },
+                Token::Limit(limit) => {
+                    res.push(Token::Limit(limit));
+                },
+                Token::Matrix(rows) => {
+                    res.push(Token::Matrix(rows));
+                },
+                Token::Identifier(ident) => {
+                    res.push(Token::Identifier(ident));
+                },
+                Token::UnaryOperation(_) | Token::BinaryOperation(_) => {
+                    res.push(token);
+                },
+                // Postfix, nothing can bind tighter
+                Token::Exclamation => {
+                    res.push(Token::UnOps(UnOps::Factorial));
+                },
                 //assume identifier
                 Token::Operator(_) => {
-                    while !stack.is_empty() && precedence(Some(&token)) <  precedence(stack.last())
-                                            || precedence(Some(&token)) == precedence(stack.last()) 
-                                            && associativity(&token) == Associativity::Left {
+                    while pops_before(&token, stack.last()) {
                         res.push(stack.pop().unwrap())
                     }
                     stack.push(token);
                 },
                 Token::BinOp(_) => {
-                    while !stack.is_empty() && precedence(Some(&token)) <  precedence(stack.last())
-                                            || precedence(Some(&token)) == precedence(stack.last()) 
-                                            && associativity(&token) == Associativity::Left {
+                    while pops_before(&token, stack.last()) {
                         res.push(stack.pop().unwrap())
                     }
                     stack.push(token);
                 },
-                Token::UnOps(_) => {
-                    while !stack.is_empty() && precedence(Some(&token)) <  precedence(stack.last())
-                                            || precedence(Some(&token)) == precedence(stack.last()) 
-                                            && associativity(&token) == Associativity::Left {
+                Token::UnOps(_) | Token::Exponent => {
+                    while pops_before(&token, stack.last()) {
                         res.push(stack.pop().unwrap())
                     }
                     stack.push(token);
                 },
-                Token::LeftParen => {
+                // Braces group like parentheses, {n + 1} * 2
+                Token::LeftParen | Token::LeftBrace => {
                     stack.push(Token::LeftParen);
                 },
-                Token::RightParen => {
+                Token::RightParen | Token::RightBrace => {
                     while !stack.is_empty() && stack.last() != Some(&Token::LeftParen) {
                         res.push(stack.pop().unwrap());
                     }
+                    stack.pop();
                 }
                 _ => panic!(),
                 // assume no Subscript identifiers, I just want a working parser for some things
@@ -238,6 +1099,9 @@ fn parse_value(tokens: TokenString) -> Value {
                 Token::Real(number) => {
                     res_2.push(Value::Number(Number::Real(number)));
                 },
+                Token::Infinity => {
+                    res_2.push(Value::Number(Number::Infinity(Sign::Plus)));
+                },
                 Token::Number(number) => {
                     res_2.push(Value::Number(Number::Integer(number.parse().unwrap())));
                 },
@@ -250,6 +1114,27 @@ fn parse_value(tokens: TokenString) -> Value {
                 Token::FunctionCall(call) => {
                     res_2.push(Value::Expression(Box::new(Expression::FunctionCall(call))));
                 },
+                Token::BuiltinCall(call) => {
+                    res_2.push(Value::Expression(Box::new(Expression::BuiltinCall(call))));
+                },
+                Token::SequenceTerm(term) => {
+                    res_2.push(Value::Expression(Box::new(Expression::SequenceTerm(term))));
+                },
+                Token::Limit(limit) => {
+                    res_2.push(Value::Expression(Box::new(Expression::Limit(limit))));
+                },
+                Token::Matrix(rows) => {
+                    res_2.push(Value::Expression(Box::new(Expression::Matrix(rows))));
+                },
+                Token::Identifier(ident) => {
+                    res_2.push(Value::Identifier(ident));
+                },
+                Token::UnaryOperation(operation) => {
+                    res_2.push(Value::Expression(Box::new(Expression::UnaryOperation(*operation))));
+                },
+                Token::BinaryOperation(operation) => {
+                    res_2.push(Value::Expression(Box::new(Expression::BinaryOperation(*operation))));
+                },
                 //assume identifier
                 Token::Operator(operator) => {
                     let value_2 = res_2.pop().unwrap();
@@ -269,27 +1154,21 @@ fn parse_value(tokens: TokenString) -> Value {
                         value_2
                     }))))
                 },
+                Token::Exponent => {
+                    let value_2 = res_2.pop().unwrap();
+                    let value_1 = res_2.pop().unwrap();
+                    res_2.push(Value::Expression(Box::new(Expression::BinaryOperation(BinaryOperation { value_1, binop: BinOps::Power, value_2 }))))
+                },
                 Token::UnOps(unop) => {
-                    res_2.push(Value::Expression(Box::new(Expression::UnaryOperation(UnaryOperation { 
-                        value: real_num_text_greek_to_val(stack.pop().unwrap()),
-                        unop,
-                    }))))
+                    let value = res_2.pop().unwrap();
+                    res_2.push(Value::Expression(Box::new(Expression::UnaryOperation(UnaryOperation { value, unop }))))
                 },
                 _ => (),
                 // assume no Subscript identifiers, I just want a working parser for some things
             }
         }
-        res_2.last().unwrap().clone()
-    }
-}
-
-fn real_num_text_greek_to_val(token: Token) -> Value {
-    match token {
-        Token::Real(number) => Value::Number(Number::Real(number)),
-        Token::Number(number) => Value::Number(Number::Integer(number.parse().unwrap())),
-        Token::Text(text) => Value::Identifier(Identifier::TextIdent(text)),
-        Token::GreekLetter(letter) => Value::Identifier(Identifier::GreekLetter(letter)),
-        _ => panic!()
+        // \max() or \sqrt at the end of a line
+        res_2.last().cloned().unwrap_or_else(|| Value::Expression(Box::new(Expression::Invalid(ParseError::MissingValue))))
     }
 }
 
@@ -323,6 +1202,78 @@ fn text_or_greek_to_ident(token: Token) -> Identifier {
     }
 }
 
+// -1 where a number can start, 2^{-1} and x = -3. A minus between two operands is still subtraction
+fn make_negative_numbers(tokens: TokenString) -> TokenString {
+    let mut res: Vec<Token> = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match (&token, tokens.peek()) {
+            (Token::Operator(Operator::Sub), Some(Token::Number(_))) if matches!(res.last(), None | Some(
+                Token::LeftParen | Token::LeftBrace | Token::Exponent | Token::Subscript | Token::Operator(_) | Token::BinOp(_)
+                | Token::Comma | Token::Equals | Token::Conditional(_) | Token::If | Token::Then | Token::Else
+            )) => {
+                let Some(Token::Number(number)) = tokens.next() else { unreachable!() };
+                res.push(Token::Number(format!("-{number}")));
+            },
+            _ => res.push(token),
+        }
+    }
+
+    res
+}
+
+// Rows split by \\, entries by &. A vmatrix is the determinant of its entries
+fn make_matrices(tokens: TokenString) -> TokenString {
+    let environment = |token: &Token, name: &str| match token {
+        Token::Command(command) if command.name == name => match &command.req[..] {
+            [environment] => match &environment[..] {
+                [Token::Text(environment)] if ["matrix", "pmatrix", "bmatrix", "Bmatrix", "vmatrix", "smallmatrix"].contains(&environment.as_str()) => Some(environment.clone()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    };
+    let mut res: Vec<Token> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let Some(name) = environment(&tokens[i], "begin") else {
+            res.push(tokens[i].clone());
+            i += 1;
+            continue;
+        };
+        let start = i + 1;
+        // Matrices of matrices end at their own \end
+        let mut depth = 1;
+        while depth > 0 {
+            i += 1;
+            match tokens.get(i) {
+                Some(token) if environment(token, "begin").is_some() => depth += 1,
+                Some(token) if environment(token, "end").is_some() => depth -= 1,
+                Some(_) => (),
+                None => {
+                    res.push(Token::ParseError(ParseError::UnclosedEnvironment(name)));
+                    return res;
+                },
+            }
+        }
+        let rows = tokens[start..i]
+            .split(|token| *token == Token::NewlineOperator)
+            .filter(|row| !row.is_empty())
+            .map(|row| row.split(|token| *token == Token::Text("&".to_owned())).map(|entry| parse_value(entry.to_vec())).collect())
+            .collect();
+        i += 1;
+        res.push(match &name[..] {
+            "vmatrix" => Token::BuiltinCall(BuiltinCall { function: Builtin::Det, args: vec![Value::Expression(Box::new(Expression::Matrix(rows)))] }),
+            _ => Token::Matrix(rows),
+        });
+    }
+
+    res
+}
+
 fn make_real_numbers(tokens: TokenString) -> TokenString {
     let mut ret = vec![];
     let string = tokens.clone();
@@ -339,7 +1290,7 @@ fn make_real_numbers(tokens: TokenString) -> TokenString {
             Token::Number(integer_part) => {
                 if let Some(Token::Period) = string.get(i+1) {
                     if let Some(Token::Number(real_part)) = string.get(i+2) {
-                        ret.push(Token::Real(format!("{integer_part}.{real_part}").parse::<f64>().unwrap()));
+                        ret.push(Token::Real(format!("{integer_part}.{real_part}").parse::<Real>().unwrap()));
                         ignore += 2;
                     }
                 } else {
@@ -358,36 +1309,15 @@ fn parse_function_def(tokens: TokenString) -> FunctionDefinition {
     // f(x) = x^2
     let (name, values) = signature.split_once(|token| *token == Token::LeftParen).unwrap();
 
-    let name = text_or_greek_to_ident(name
-        .iter()
-        .filter(|token| {
-            if let Token::GreekLetter(_) = token {
-                return true;
-            }
-            if let Token::Text(_) = token {
-                return true;
-            }
-            false
-        })
-        .cloned()
-        .collect::<Vec<Token>>()
-        .first()
-        .unwrap().clone());
-
-    let args = values.iter()
-        .filter(|token| {
-            if let Token::GreekLetter(_) = token {
-                return true;
-            }
-            if let Token::Text(_) = token {
-                return true;
-            }
-            false
-        })
-        .map(|token| text_or_greek_to_ident(token.clone()))
+    // Name and arguments can be subscripted, f_1(x_1, x_2) = x_1 + x_2
+    let name = parse_identifier(name.to_vec());
+
+    let args = values
+        .split(|token| *token == Token::Comma)
+        .map(|arg| arg.iter().filter(|token| **token != Token::RightParen).cloned().collect::<TokenString>())
+        .filter(|arg| !arg.is_empty())
+        .map(parse_identifier)
         .collect::<Vec<Identifier>>();
-    
-    // Name must be text or greek letter
 
     FunctionDefinition { 
         identifier: name,
@@ -396,14 +1326,24 @@ fn parse_function_def(tokens: TokenString) -> FunctionDefinition {
     }
 }
 
+fn parse_sequence_def(tokens: TokenString) -> SequenceDefinition {
+    let (signature, expression) = tokens.split_once(|token| *token == Token::Equals).unwrap();
+    // a_n = a_{n-1} + a_{n-2}
+    let Identifier::SubScriptIdent(signature) = parse_identifier(signature.to_vec()) else {
+        panic!("Sequence definitions must be subscripted")
+    };
+
+    SequenceDefinition {
+        identifier: signature.first_ident,
+        index:      signature.secnd_ident,
+        expression: parse_expression(expression.to_vec())
+    }
+}
+
 fn parse_declaration(tokens: TokenString) -> Declaration {
     match tokens.split_once(|elem| *elem == Token::Equals) {
         Some((ident, expression)) => {
             let identifier = parse_identifier(ident.to_vec());
-            // Value or set now
-            if expression.contains(&Token::RightBrace) {
-                todo!()
-            } 
             // Value
             let value = parse_value(expression.to_vec());
             Declaration::ValueDeclaration(ValueDeclaration { identifier, value })
@@ -411,6 +1351,11 @@ fn parse_declaration(tokens: TokenString) -> Declaration {
         None => {
             // Probably a function 🤷
             let tokens = tokens.into_iter().filter(|token| *token != Token::Space).collect::<Vec<Token>>();
+            // \memo f: \N \rightarrow \N
+            let (memoized, tokens) = match tokens.split_first() {
+                Some((Token::Memo, rest)) => (true, rest.to_vec()),
+                _ => (false, tokens),
+            };
             // Gonna assume everything is correct syntax
             let (ident, definition) = tokens.split_once(|token| *token == Token::Colon).unwrap();
             let identifier = parse_identifier(ident.to_vec());
@@ -419,7 +1364,7 @@ fn parse_declaration(tokens: TokenString) -> Declaration {
             // ComplexNumbers, RationalNumbers, Integers]
             let domain   = domain.first().unwrap().clone();
             let codomain = codomain.first().unwrap().clone();
-            Declaration::FunctionDeclaration(FunctionDeclaration { identifier, domain, codomain })
+            Declaration::FunctionDeclaration(Box::new(FunctionDeclaration { identifier, domain, codomain, memoized }))
         }
     }
 }
@@ -428,7 +1373,12 @@ fn parse_identifier(tokens: TokenString) -> Identifier {
     if tokens.contains(&Token::Subscript) {
         let (first_ident, secnd_ident) = tokens.split_once(|elem| *elem == Token::Subscript).unwrap();
         let first_ident = parse_identifier(first_ident.to_vec());
-        let secnd_ident = parse_identifier(secnd_ident.to_vec());
+        // a_{10}, the braces only group the subscript
+        let secnd_ident = parse_identifier(secnd_ident
+            .iter()
+            .filter(|token| **token != Token::LeftBrace && **token != Token::RightBrace)
+            .cloned()
+            .collect());
 
         return Identifier::SubScriptIdent(Box::new(
             SubScriptIdent { 
@@ -445,42 +1395,49 @@ fn parse_identifier(tokens: TokenString) -> Identifier {
         Some(Token::GreekLetter(greek_letter)) => {
             Identifier::GreekLetter(greek_letter.to_owned())
         },
+        Some(Token::Number(number)) => {
+            Identifier::NumberIdent(number.parse().unwrap())
+        },
         _ => panic!("Invalid syntax")
     }
 }
 
-fn into_slices(tokens: TokenString) -> Vec<Vec<Token>> {
-    let mut slices: Vec<Vec<Token>> = vec![];
-
-    let mut curr_slice: Vec<Token> = vec![];
-
-    let mut balanced = 0;
+// Statements end at commas, semicolons and \\ outside of any brackets, braces are kept for grouping
+fn into_statements(tokens: TokenString) -> Vec<TokenString> {
+    let mut statements: Vec<TokenString> = vec![];
+    let mut statement: TokenString = vec![];
+    let mut depth = 0usize;
 
-    for token in tokens {
+    for (i, token) in tokens.iter().enumerate() {
         match token {
-            Token::Comma => {
-                if balanced == 0 {
-                    slices.push(curr_slice);
-                    curr_slice = vec![];
-                } else {
-                    curr_slice.push(Token::Comma);
+            Token::LeftParen  | Token::LeftBrace  | Token::EscapedLeftBrace  => depth += 1,
+            Token::RightParen | Token::RightBrace | Token::EscapedRightBrace => depth = depth.saturating_sub(1),
+            // The \\ between the rows of a matrix doesn't end the statement, or a comma in \langle a, b \rangle
+            Token::Command(command) if command.name == "begin" || command.name == "langle" => depth += 1,
+            Token::Command(command) if command.name == "end" || command.name == "rangle"   => depth = depth.saturating_sub(1),
+            Token::Comma | Token::Semicolon if depth == 0 => {
+                statements.push(std::mem::take(&mut statement));
+                continue;
+            },
+            Token::NewlineOperator if depth == 0 => {
+                if !continues_line(&statement, tokens.get(i + 1)) {
+                    statements.push(std::mem::take(&mut statement));
                 }
+                continue;
             },
-            Token::LeftBrace  => balanced += 1,
-            Token::RightBrace => balanced -= 1,
-            Token::LeftParen  => {
-                balanced += 1;
-                curr_slice.push(Token::LeftParen);
-            }
-            Token::RightParen => {
-                balanced -= 1;
-                curr_slice.push(Token::RightParen);
-            }
-            token => curr_slice.push(token),
+            _ => (),
         }
+        statement.push(token.clone());
     }
+    statements.push(statement);
 
-    slices.push(curr_slice);
+    statements.into_iter().filter(|statement| !statement.is_empty()).collect()
+}
 
-    slices.into_iter().filter(|slice| !slice.is_empty()).collect()
+// f(n) = \if n \\ \then 1 \\ \else 2 is one statement over three lines
+fn continues_line(statement: &[Token], next: Option<&Token>) -> bool {
+    let unfinished = matches!(statement.last(), Some(
+        Token::Equals | Token::Colon | Token::RightArrow | Token::Operator(_) | Token::BinOp(_) | Token::If | Token::Then | Token::Else
+    ));
+    unfinished || matches!(next, Some(Token::Then | Token::Else))
 }
@@ -0,0 +1,466 @@
+use std::fmt::Display;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use num::complex::Complex64;
+use num::{BigInt, BigRational, Integer, One, Signed, ToPrimitive, Zero};
+
+use crate::ast_types::Identifier;
+use crate::modular;
+use crate::symbolic::{self, Symbolic};
+
+// How many candidate factors Kronecker's method tries for each degree before giving up
+const CANDIDATE_LIMIT: usize = 200_000;
+// Residues tried for the roots of a polynomial over \Z/n\Z
+const RESIDUE_LIMIT: u64 = 1_000_000;
+
+// Where the coefficients are, from the domain of the function the polynomial is defined as
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ring {
+    Integers,
+    Rationals,
+    // Kept as their exact rational value
+    Reals,
+    // \Z/n\Z, coefficients between 0 and n - 1
+    Modular(BigInt),
+}
+
+impl Ring {
+    // The larger of two rings, \Z is in \Q which is in \R. \Z/n\Z only goes with itself
+    pub fn join(&self, other: &Ring) -> Option<Ring> {
+        let rank = |ring: &Ring| match ring {
+            Ring::Integers   => Some(0),
+            Ring::Rationals  => Some(1),
+            Ring::Reals      => Some(2),
+            Ring::Modular(_) => None,
+        };
+        match (rank(self), rank(other)) {
+            (Some(a), Some(b)) => Some(if a >= b { self.clone() } else { other.clone() }),
+            _ => (self == other).then(|| self.clone()),
+        }
+    }
+}
+
+// p(x) = x^3 - 2x + 1, coefficients lowest degree first without trailing zeros
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Polynomial {
+    pub variable:     Identifier,
+    pub ring:         Ring,
+    pub coefficients: Vec<BigRational>,
+}
+
+impl Polynomial {
+    // None when a coefficient isn't in the ring, like 1/2 in \Z or \Z/4\Z
+    pub fn new(variable: Identifier, ring: Ring, coefficients: Vec<BigRational>) -> Option<Self> {
+        let coefficients = match &ring {
+            Ring::Integers => coefficients.into_iter().map(|coefficient| coefficient.is_integer().then_some(coefficient)).collect::<Option<_>>()?,
+            // a/b is a b^{-1}
+            Ring::Modular(n) => coefficients.into_iter().map(|coefficient| modular::residue(&coefficient, n).map(BigRational::from_integer)).collect::<Option<_>>()?,
+            Ring::Rationals | Ring::Reals => coefficients,
+        };
+        let mut polynomial = Self { variable, ring, coefficients };
+        trim(&mut polynomial.coefficients);
+        Some(polynomial)
+    }
+
+    // A polynomial in x and nothing else
+    pub fn from_symbolic(symbolic: &Symbolic, x: &Identifier, ring: Ring) -> Option<Self> {
+        let (numerator, mut denominator) = symbolic.rational_function(x)?;
+        trim(&mut denominator);
+        let [constant] = &denominator[..] else { return None };
+        Self::new(x.clone(), ring, numerator.iter().map(|coefficient| coefficient / constant).collect())
+    }
+
+    // The same ring and variable
+    fn with_coefficients(&self, coefficients: Vec<BigRational>) -> Self {
+        let mut coefficients = coefficients.into_iter().map(|coefficient| self.reduce(coefficient)).collect();
+        trim(&mut coefficients);
+        Self { variable: self.variable.clone(), ring: self.ring.clone(), coefficients }
+    }
+
+    pub fn constant(&self, constant: BigRational) -> Option<Self> {
+        Self::new(self.variable.clone(), self.ring.clone(), vec![constant])
+    }
+
+    // None for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    pub fn evaluate(&self, x: &BigRational) -> BigRational {
+        self.reduce(evaluate(&self.coefficients, x))
+    }
+
+    pub fn derivative(&self) -> Self {
+        self.with_coefficients(self.coefficients.iter().enumerate().skip(1).map(|(degree, coefficient)| coefficient * BigInt::from(degree)).collect())
+    }
+
+    pub fn pow(&self, n: u32) -> Self {
+        (0..n).fold(self.with_coefficients(vec![BigRational::one()]), |power, _| &power * self)
+    }
+
+    // Everything but \Z/n\Z coefficients is left as it is, they are always whole
+    fn reduce(&self, coefficient: BigRational) -> BigRational {
+        match &self.ring {
+            Ring::Modular(n) => BigRational::from_integer(coefficient.to_integer().mod_floor(n)),
+            _ => coefficient,
+        }
+    }
+
+    // Worked out over \Q for \Z, whether the result is whole is up to the caller
+    fn invert(&self, coefficient: &BigRational) -> Option<BigRational> {
+        match &self.ring {
+            Ring::Modular(n) => modular::inverse(&coefficient.to_integer(), n).map(BigRational::from_integer),
+            _ if coefficient.is_zero() => None,
+            _ => Some(coefficient.recip()),
+        }
+    }
+
+    // Long division, None when the leading coefficient of the divisor can't be divided by or the
+    // quotient has coefficients outside of the ring
+    pub fn div_rem(&self, divisor: &Self) -> Option<(Self, Self)> {
+        let inverse = self.invert(divisor.coefficients.last()?)?;
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec![BigRational::zero(); (remainder.len() + 1).saturating_sub(divisor.coefficients.len())];
+        while remainder.len() >= divisor.coefficients.len() {
+            let shift = remainder.len() - divisor.coefficients.len();
+            let factor = self.reduce(&remainder[remainder.len() - 1] * &inverse);
+            for (degree, coefficient) in divisor.coefficients.iter().enumerate() {
+                remainder[shift + degree] = self.reduce(&remainder[shift + degree] - &factor * coefficient);
+            }
+            quotient[shift] = factor;
+            trim(&mut remainder);
+        }
+        Some((Self::new(self.variable.clone(), self.ring.clone(), quotient)?, Self::new(self.variable.clone(), self.ring.clone(), remainder)?))
+    }
+
+    // Monic over a field. Over \Z the gcd of the contents times a primitive polynomial
+    pub fn gcd(&self, other: &Self) -> Option<Self> {
+        let field = |polynomial: &Self| match polynomial.ring {
+            Ring::Integers => Self { ring: Ring::Rationals, ..polynomial.clone() },
+            _ => polynomial.clone(),
+        };
+        let (mut a, mut b) = (field(self), field(other));
+        while !b.is_zero() {
+            let (_, remainder) = a.div_rem(&b)?;
+            (a, b) = (b, remainder);
+        }
+        let Some(leading) = a.coefficients.last() else {
+            return Some(self.with_coefficients(vec![]));
+        };
+        let inverse = a.invert(leading)?;
+        let monic = a.coefficients.iter().map(|coefficient| a.reduce(coefficient * &inverse)).collect::<Vec<_>>();
+        match self.ring {
+            Ring::Integers => {
+                let content = content(&self.coefficients).gcd(&content(&other.coefficients));
+                Some(self.with_coefficients(primitive(&monic).into_iter().map(|coefficient| BigRational::from_integer(coefficient * &content)).collect()))
+            },
+            _ => Some(self.with_coefficients(monic)),
+        }
+    }
+
+    // Into irreducible factors over \Q, with their multiplicity, and the constant in front.
+    // The factors have whole coefficients and a positive leading one. None over \Z/n\Z
+    pub fn factor(&self) -> Option<(BigRational, Vec<(Self, usize)>)> {
+        if matches!(self.ring, Ring::Modular(_)) {
+            return None;
+        }
+        let Some(leading) = self.coefficients.last() else {
+            return Some((BigRational::zero(), vec![]));
+        };
+        let whole = |coefficients: &[BigInt]| Self {
+            variable: self.variable.clone(),
+            ring: Ring::Integers,
+            coefficients: coefficients.iter().cloned().map(BigRational::from_integer).collect(),
+        };
+        let primitive = primitive(&self.coefficients);
+        let constant = leading / BigRational::from_integer(primitive[primitive.len() - 1].clone());
+
+        let (linear, rest) = linear_factors(primitive);
+        let mut factors = linear.into_iter()
+            .map(|((q, p), multiplicity)| (whole(&[-p, q]), multiplicity as usize))
+            .collect::<Vec<_>>();
+        // Without linear factors what's left has none of degree 1, so the first factor found for
+        // each degree is irreducible
+        let mut rest = whole(&rest);
+        let mut degree = 2;
+        while 2 * degree <= rest.degree().unwrap_or(0) {
+            let Some(factor) = kronecker(&rest, degree) else {
+                degree += 1;
+                continue;
+            };
+            let mut multiplicity = 0;
+            while let Some((quotient, _)) = rest.div_rem(&factor).filter(|(_, remainder)| remainder.is_zero()) {
+                rest = quotient;
+                multiplicity += 1;
+            }
+            factors.push((factor, multiplicity));
+        }
+        if rest.degree().is_some_and(|degree| degree > 0) {
+            factors.push((rest, 1));
+        }
+        Some((constant, factors))
+    }
+
+    // The distinct roots, exact when they're rational and numerical otherwise. Over \Z/n\Z every
+    // residue is tried, None when there are too many
+    pub fn roots(&self) -> Option<(Vec<BigRational>, Vec<Complex64>)> {
+        if let Ring::Modular(n) = &self.ring {
+            let residues = n.to_u64().filter(|n| *n <= RESIDUE_LIMIT)?;
+            let roots = (0..residues)
+                .map(|residue| BigRational::from_integer(residue.into()))
+                .filter(|residue| self.evaluate(residue).is_zero())
+                .collect();
+            return Some((roots, vec![]));
+        }
+        if self.degree().is_none_or(|degree| degree == 0) {
+            return Some((vec![], vec![]));
+        }
+        let (linear, rest) = linear_factors(primitive(&self.coefficients));
+        let mut exact = linear.into_iter().map(|((q, p), _)| BigRational::new(p, q)).collect::<Vec<_>>();
+        exact.sort();
+        // Repeated roots slow the iteration down, so they're divided out first
+        let rest = Self { variable: self.variable.clone(), ring: Ring::Rationals, coefficients: rest.into_iter().map(BigRational::from_integer).collect() };
+        let square_free = match rest.gcd(&rest.derivative()) {
+            Some(gcd) if !gcd.is_zero() => rest.div_rem(&gcd)?.0,
+            _ => rest,
+        };
+        let coefficients = square_free.coefficients.iter().map(|coefficient| coefficient.to_f64()).collect::<Option<Vec<_>>>()?;
+        Some((exact, durand_kerner(&coefficients)))
+    }
+
+    pub fn to_symbolic(&self) -> Symbolic {
+        let x = Symbolic::Variable(self.variable.clone());
+        self.coefficients.iter().enumerate().fold(symbolic::integer(0), |sum, (degree, coefficient)| {
+            symbolic::add(sum, symbolic::mul(Symbolic::Constant(coefficient.clone()), symbolic::pow(x.clone(), symbolic::integer(degree as i64))))
+        })
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: &Polynomial) -> Polynomial {
+        self.with_coefficients(add(&self.coefficients, &other.coefficients))
+    }
+}
+
+impl Neg for &Polynomial {
+    type Output = Polynomial;
+
+    fn neg(self) -> Polynomial {
+        self.with_coefficients(self.coefficients.iter().map(|coefficient| -coefficient).collect())
+    }
+}
+
+impl Sub for &Polynomial {
+    type Output = Polynomial;
+
+    fn sub(self, other: &Polynomial) -> Polynomial {
+        self + &-other
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        self.with_coefficients(mul(&self.coefficients, &other.coefficients))
+    }
+}
+
+// x^3 - 2x + 1
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_symbolic())
+    }
+}
+
+// Coefficients lowest degree first, as used by symbolic and limit
+pub fn add(p: &[BigRational], q: &[BigRational]) -> Vec<BigRational> {
+    (0..p.len().max(q.len())).map(|i| p.get(i).cloned().unwrap_or_default() + q.get(i).cloned().unwrap_or_default()).collect()
+}
+
+// Every product of a term of p and a term of q
+pub fn mul(p: &[BigRational], q: &[BigRational]) -> Vec<BigRational> {
+    let mut product = vec![BigRational::zero(); (p.len() + q.len()).saturating_sub(1)];
+    for (i, a) in p.iter().enumerate() {
+        for (j, b) in q.iter().enumerate() {
+            product[i + j] += a * b;
+        }
+    }
+    product
+}
+
+pub fn trim(coefficients: &mut Vec<BigRational>) {
+    while coefficients.last().is_some_and(Zero::is_zero) {
+        coefficients.pop();
+    }
+}
+
+pub fn evaluate(coefficients: &[BigRational], x: &BigRational) -> BigRational {
+    coefficients.iter().rev().fold(BigRational::zero(), |value, coefficient| value * x + coefficient)
+}
+
+// p(x) / (x - a) for a root a of p, by synthetic division
+pub fn divide_root(coefficients: &[BigRational], a: &BigRational) -> Vec<BigRational> {
+    let mut quotient = vec![BigRational::zero(); coefficients.len().saturating_sub(1)];
+    let mut carry = BigRational::zero();
+    for i in (1..coefficients.len()).rev() {
+        carry = &coefficients[i] + carry * a;
+        quotient[i - 1] = carry.clone();
+    }
+    quotient
+}
+
+// qx - p as (q, p), with its multiplicity
+pub type LinearFactor = ((BigInt, BigInt), i64);
+
+// Roots p/q found with the rational root theorem, as factors qx - p with their multiplicity,
+// and what is left of the polynomial after dividing them out
+pub fn linear_factors(mut coefficients: Vec<BigInt>) -> (Vec<LinearFactor>, Vec<BigInt>) {
+    let mut factors: Vec<LinearFactor> = vec![];
+    'search: while coefficients.len() > 1 {
+        let (constant, leading) = (&coefficients[0], &coefficients[coefficients.len() - 1]);
+        // x is a factor, 0 a root
+        let candidates = match constant.is_zero() {
+            true  => Some((vec![BigInt::zero()], vec![BigInt::one()])),
+            false => divisors(constant).zip(divisors(leading)),
+        };
+        let Some((ps, qs)) = candidates else {
+            break;
+        };
+        for q in &qs {
+            for p in ps.iter().flat_map(|p| [p.clone(), -p]) {
+                if !p.gcd(q).is_one() {
+                    continue;
+                }
+                let root = BigRational::new(p.clone(), q.clone());
+                let value = coefficients.iter().rev().fold(BigRational::zero(), |value, coefficient| value * &root + BigRational::from_integer(coefficient.clone()));
+                if !value.is_zero() {
+                    continue;
+                }
+                // Synthetic division by qx - p keeps the coefficients whole
+                let mut quotient = vec![BigInt::zero(); coefficients.len() - 1];
+                let mut carry = BigInt::zero();
+                for degree in (1..coefficients.len()).rev() {
+                    carry = (&coefficients[degree] + &carry * &p) / q;
+                    quotient[degree - 1] = carry.clone();
+                }
+                coefficients = quotient;
+                match factors.iter_mut().find(|(factor, _)| *factor == (q.clone(), p.clone())) {
+                    Some((_, multiplicity)) => *multiplicity += 1,
+                    None => factors.push(((q.clone(), p), 1)),
+                }
+                continue 'search;
+            }
+        }
+        break;
+    }
+    (factors, coefficients)
+}
+
+// Positive divisors, as long as there aren't too many to try
+fn divisors(n: &BigInt) -> Option<Vec<BigInt>> {
+    let n = n.abs().to_u64().filter(|n| (1..=1_000_000).contains(n))?;
+    Some((1..=n).filter(|d| n % d == 0).map(BigInt::from).collect())
+}
+
+// The gcd of whole coefficients
+fn content(coefficients: &[BigRational]) -> BigInt {
+    coefficients.iter().fold(BigInt::zero(), |gcd, coefficient| gcd.gcd(&coefficient.to_integer()))
+}
+
+// Whole coefficients with no common factor and a positive leading one
+fn primitive(coefficients: &[BigRational]) -> Vec<BigInt> {
+    let denominators = coefficients.iter().fold(BigInt::one(), |lcm, coefficient| lcm.lcm(coefficient.denom()));
+    let whole = coefficients.iter().map(|coefficient| (coefficient * &denominators).to_integer()).collect::<Vec<_>>();
+    let mut gcd = whole.iter().fold(BigInt::zero(), |gcd, coefficient| gcd.gcd(coefficient));
+    if whole.last().is_some_and(Signed::is_negative) {
+        gcd = -gcd;
+    }
+    whole.into_iter().map(|coefficient| coefficient / &gcd).collect()
+}
+
+// Kronecker's method for a factor of f with whole coefficients of the given degree. The factor
+// is fixed by its values at degree + 1 points, which have to divide the values of f there
+fn kronecker(f: &Polynomial, degree: usize) -> Option<Polynomial> {
+    let points = (0..).map(|i: i64| if i % 2 == 0 { -i / 2 } else { (i + 1) / 2 })
+        .map(|point| BigRational::from_integer(point.into()))
+        .filter(|point| !f.evaluate(point).is_zero())
+        .take(degree + 1)
+        .collect::<Vec<_>>();
+    // Up to sign, so the value at the first point is positive
+    let choices = points.iter().enumerate().map(|(i, point)| {
+        let divisors = divisors(&f.evaluate(point).to_integer())?;
+        Some(match i {
+            0 => divisors,
+            _ => divisors.iter().flat_map(|divisor| [divisor.clone(), -divisor]).collect(),
+        })
+    }).collect::<Option<Vec<_>>>()?;
+    let candidates = choices.iter().try_fold(1usize, |product, choice| product.checked_mul(choice.len()))?;
+    if candidates > CANDIDATE_LIMIT {
+        return None;
+    }
+    (0..candidates).find_map(|mut index| {
+        let values = choices.iter().map(|choice| {
+            let value = BigRational::from_integer(choice[index % choice.len()].clone());
+            index /= choice.len();
+            value
+        }).collect::<Vec<_>>();
+        let factor = Polynomial::new(f.variable.clone(), Ring::Integers, interpolate(&points, &values))?;
+        if factor.degree() != Some(degree) {
+            return None;
+        }
+        let (_, remainder) = f.div_rem(&factor)?;
+        remainder.is_zero().then(|| match factor.coefficients.last().is_some_and(Signed::is_negative) {
+            true  => -&factor,
+            false => factor,
+        })
+    })
+}
+
+// The polynomial through the points, by Lagrange interpolation
+fn interpolate(points: &[BigRational], values: &[BigRational]) -> Vec<BigRational> {
+    let mut result = vec![];
+    for (i, (a, value)) in points.iter().zip(values).enumerate() {
+        let mut basis = vec![value.clone()];
+        for (_, b) in points.iter().enumerate().filter(|(j, _)| *j != i) {
+            basis = mul(&basis, &[-b / (a - b), (a - b).recip()]);
+        }
+        result = add(&result, &basis);
+    }
+    result
+}
+
+// The Weierstrass iteration, which moves every root at once
+fn durand_kerner(coefficients: &[f64]) -> Vec<Complex64> {
+    let Some(degree) = coefficients.len().checked_sub(1).filter(|degree| *degree > 0) else {
+        return vec![];
+    };
+    let monic = coefficients.iter().map(|coefficient| coefficient / coefficients[degree]).collect::<Vec<_>>();
+    let p = |z: Complex64| monic.iter().rev().fold(Complex64::zero(), |value, coefficient| value * z + coefficient);
+    let seed = Complex64::new(0.4, 0.9);
+    let mut roots = (0..degree).map(|k| seed.powu(k as u32)).collect::<Vec<_>>();
+    for _ in 0..1000 {
+        let mut change: f64 = 0.0;
+        for i in 0..degree {
+            let denominator = (0..degree).filter(|j| *j != i).fold(Complex64::one(), |product, j| product * (roots[i] - roots[j]));
+            let step = p(roots[i]) / denominator;
+            roots[i] -= step;
+            change = change.max(step.norm());
+        }
+        if change < 1e-15 {
+            break;
+        }
+    }
+    for root in &mut roots {
+        if root.im.abs() < 1e-10 * root.norm().max(1.0) {
+            root.im = 0.0;
+        }
+    }
+    roots
+}
@@ -0,0 +1,307 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use num::{BigInt, BigRational, Integer, Signed, ToPrimitive, Zero};
+
+// More digits than anyone should ask for, rounded to the working precision when used
+const PI:           &str = "3.14159265358979323846264338327950288419716939937510582097494459";
+const E:            &str = "2.71828182845904523536028747135266249775724709369995957496696763";
+const GOLDEN_RATIO: &str = "1.61803398874989484820458683436563811772030917980576286213544862";
+
+// A decimal, mantissa * 10^exponent. Kept normalised so equal values have equal representations
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Real {
+    mantissa: BigInt,
+    exponent: i64,
+}
+
+// How digits that don't fit are dropped, %jimtex: rounding half-even
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    #[default]
+    HalfEven,
+    HalfUp,
+    // Towards zero
+    Down,
+    // Away from zero
+    Up,
+    Floor,
+    Ceiling,
+}
+
+// Significant digits kept by division, constants and anything else that can't be exact
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealContext {
+    pub digits:   usize,
+    pub rounding: Rounding,
+}
+
+impl Default for RealContext {
+    fn default() -> Self {
+        Self { digits: 30, rounding: Rounding::default() }
+    }
+}
+
+impl Rounding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "half-even" => Rounding::HalfEven,
+            "half-up"   => Rounding::HalfUp,
+            "down"      => Rounding::Down,
+            "up"        => Rounding::Up,
+            "floor"     => Rounding::Floor,
+            "ceiling"   => Rounding::Ceiling,
+            _ => return None,
+        })
+    }
+}
+
+fn power_of_ten(exponent: i64) -> BigInt {
+    num::pow(BigInt::from(10u8), exponent as usize)
+}
+
+fn digit_count(int: &BigInt) -> i64 {
+    if int.is_zero() { 1 } else { int.abs().to_string().len() as i64 }
+}
+
+impl Real {
+    fn new(mantissa: BigInt, exponent: i64) -> Self {
+        Self { mantissa, exponent }.normalise()
+    }
+
+    fn normalise(mut self) -> Self {
+        if self.mantissa.is_zero() {
+            self.exponent = 0;
+            return self;
+        }
+        let ten = BigInt::from(10u8);
+        while (&self.mantissa % &ten).is_zero() {
+            self.mantissa /= &ten;
+            self.exponent += 1;
+        }
+        self
+    }
+
+    pub fn pi(context: &RealContext) -> Self {
+        PI.parse::<Real>().unwrap().round_significant(context)
+    }
+
+    pub fn e(context: &RealContext) -> Self {
+        E.parse::<Real>().unwrap().round_significant(context)
+    }
+
+    pub fn golden_ratio(context: &RealContext) -> Self {
+        GOLDEN_RATIO.parse::<Real>().unwrap().round_significant(context)
+    }
+
+    // The shortest decimal that reads back as the same double, 0.1 and not 0.1000000000000000055...
+    pub fn from_f64(real: f64) -> Option<Self> {
+        if !real.is_finite() {
+            return None;
+        }
+        format!("{real:?}").parse().ok()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa.is_negative()
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { mantissa: self.mantissa.abs(), exponent: self.exponent }
+    }
+
+    // Both with the smaller exponent
+    fn align(&self, other: &Self) -> (BigInt, BigInt, i64) {
+        let exponent = self.exponent.min(other.exponent);
+        (
+            &self.mantissa  * power_of_ten(self.exponent  - exponent),
+            &other.mantissa * power_of_ten(other.exponent - exponent),
+            exponent,
+        )
+    }
+
+    // To a multiple of 10^exponent
+    pub fn round_at(&self, exponent: i64, rounding: Rounding) -> Self {
+        if self.exponent >= exponent {
+            return self.clone();
+        }
+        let divisor = power_of_ten(exponent - self.exponent);
+        let (quotient, remainder) = self.mantissa.div_rem(&divisor);
+        if remainder.is_zero() {
+            return Self::new(quotient, exponent);
+        }
+        let half = (remainder.abs() * 2u8).cmp(&divisor);
+        let away = match rounding {
+            Rounding::Down     => false,
+            Rounding::Up       => true,
+            Rounding::Floor    => self.is_negative(),
+            Rounding::Ceiling  => !self.is_negative(),
+            Rounding::HalfUp   => half != Ordering::Less,
+            Rounding::HalfEven => half == Ordering::Greater || (half == Ordering::Equal && quotient.is_odd()),
+        };
+        let quotient = if away { quotient + self.mantissa.signum() } else { quotient };
+        Self::new(quotient, exponent)
+    }
+
+    pub fn round_significant(&self, context: &RealContext) -> Self {
+        let exponent = self.exponent + digit_count(&self.mantissa) - context.digits as i64;
+        self.round_at(exponent, context.rounding)
+    }
+
+    // m * 10^e with 1 <= |m| < 10, m rounded to digits significant digits
+    pub fn to_scientific(&self, digits: usize, rounding: Rounding) -> (Self, i64) {
+        if self.is_zero() {
+            return (self.clone(), 0);
+        }
+        let rounded = self.round_significant(&RealContext { digits, rounding });
+        let places = digit_count(&rounded.mantissa) - 1;
+        (Self::new(rounded.mantissa, -places), rounded.exponent + places)
+    }
+
+    pub fn div(&self, other: &Self, context: &RealContext) -> Self {
+        // Enough digits for the precision, plus one to round with
+        let shift = (context.digits as i64 + 1 + digit_count(&other.mantissa) - digit_count(&self.mantissa)).max(0);
+        let (quotient, remainder) = (&self.mantissa * power_of_ten(shift)).div_rem(&other.mantissa);
+        let mut quotient = Self { mantissa: quotient, exponent: self.exponent - other.exponent - shift };
+        // Whatever is left over only matters for deciding ties, so it's kept as a trailing 1
+        if !remainder.is_zero() {
+            let sign = if remainder.is_negative() != other.mantissa.is_negative() { -1 } else { 1 };
+            quotient = Self { mantissa: quotient.mantissa * 10u8 + sign, exponent: quotient.exponent - 1 };
+        }
+        quotient.round_significant(context).normalise()
+    }
+
+    // Exact, a - b * floor(a / b) with the remainder taking the sign of b
+    pub fn rem_euclid(&self, other: &Self) -> Self {
+        let (a, b, exponent) = self.align(other);
+        Self::new(a.mod_floor(&b.abs()), exponent)
+    }
+
+    pub fn floor(&self) -> BigInt {
+        self.round_at(0, Rounding::Floor).to_integer()
+    }
+
+    pub fn ceil(&self) -> BigInt {
+        self.round_at(0, Rounding::Ceiling).to_integer()
+    }
+
+    // Only meant for reals that are already whole
+    fn to_integer(&self) -> BigInt {
+        if self.exponent >= 0 {
+            &self.mantissa * power_of_ten(self.exponent)
+        } else {
+            &self.mantissa / power_of_ten(-self.exponent)
+        }
+    }
+
+    pub fn to_rational(&self) -> BigRational {
+        if self.exponent >= 0 {
+            BigRational::from_integer(self.to_integer())
+        } else {
+            BigRational::new(self.mantissa.clone(), power_of_ten(-self.exponent))
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        self.to_integer().to_i64()
+    }
+
+    // Exactly places digits after the point, %jimtex: precision
+    pub fn to_fixed(&self, places: usize, rounding: Rounding) -> String {
+        let rounded = self.round_at(-(places as i64), rounding);
+        let scaled = &rounded.mantissa * power_of_ten(rounded.exponent + places as i64);
+        let digits = format!("{:0>width$}", scaled.abs().to_string(), width = places + 1);
+        let sign = if scaled.is_negative() { "-" } else { "" };
+        if places == 0 {
+            return format!("{sign}{digits}");
+        }
+        let (integer, fraction) = digits.split_at(digits.len() - places);
+        format!("{sign}{integer}.{fraction}")
+    }
+}
+
+impl From<BigInt> for Real {
+    fn from(int: BigInt) -> Self {
+        Self::new(int, 0)
+    }
+}
+
+impl Add for Real {
+    type Output = Real;
+    fn add(self, other: Self) -> Self::Output {
+        let (a, b, exponent) = self.align(&other);
+        Self::new(a + b, exponent)
+    }
+}
+
+impl Sub for Real {
+    type Output = Real;
+    fn sub(self, other: Self) -> Self::Output {
+        self + -other
+    }
+}
+
+impl Mul for Real {
+    type Output = Real;
+    fn mul(self, other: Self) -> Self::Output {
+        Self::new(self.mantissa * other.mantissa, self.exponent + other.exponent)
+    }
+}
+
+impl Neg for Real {
+    type Output = Real;
+    fn neg(self) -> Self::Output {
+        Self { mantissa: -self.mantissa, exponent: self.exponent }
+    }
+}
+
+impl PartialOrd for Real {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Real {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.align(other);
+        a.cmp(&b)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseRealError;
+
+// 12.5, -0.001, 1e-7, 6.02e23
+impl FromStr for Real {
+    type Err = ParseRealError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (number, exponent) = match string.split_once(['e', 'E']) {
+            Some((number, exponent)) => (number, exponent.parse::<i64>().map_err(|_| ParseRealError)?),
+            None => (string, 0),
+        };
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if !fraction.chars().all(|char| char.is_ascii_digit()) {
+            return Err(ParseRealError);
+        }
+        let mantissa = format!("{integer}{fraction}").parse::<BigInt>().map_err(|_| ParseRealError)?;
+        Ok(Self::new(mantissa, exponent - fraction.len() as i64))
+    }
+}
+
+// Every digit there is, with at least one after the point, 15.6 and 3.0
+impl Display for Real {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_fixed((-self.exponent).max(1) as usize, Rounding::Down))
+    }
+}
@@ -0,0 +1,722 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use num::{BigInt, BigRational, Integer, One, Signed, ToPrimitive, Zero};
+
+use crate::ast::{BinOps, Builtin, GreekLetters, UnOps};
+use crate::ast_types::{BinaryOperation, BuiltinCall, Expression, Identifier, Number, UnaryOperation, Value};
+use crate::polynomial::{self, Polynomial, Ring};
+use crate::real::Real;
+
+// An expression with free variables, %jimtex: symbolic. Only built through add, mul, pow and
+// call, which keep it in a canonical form so equal expressions are always written the same way
+#[derive(Debug, Clone, PartialEq)]
+pub enum Symbolic {
+    Constant(BigRational),
+    Variable(Identifier),
+    // Like terms combined, highest degree first and the constant last
+    Sum(Vec<Symbolic>),
+    // A coefficient other than 1 first, then one factor for each base
+    Product(Vec<Symbolic>),
+    Power(Box<Symbolic>, Box<Symbolic>),
+    // \sin x, \log_b x with the base last like BuiltinCall
+    Call(Builtin, Vec<Symbolic>),
+    // |x|, \lfloor x \rfloor, x!
+    Operation(UnOps, Box<Symbolic>),
+}
+
+pub fn integer(int: i64) -> Symbolic {
+    Symbolic::Constant(BigRational::from_integer(int.into()))
+}
+
+pub fn variable(name: &str) -> Symbolic {
+    Symbolic::Variable(Identifier::TextIdent(name.to_owned()))
+}
+
+fn pi() -> Symbolic {
+    Symbolic::Variable(Identifier::GreekLetter(GreekLetters::Pi))
+}
+
+pub fn add(a: Symbolic, b: Symbolic) -> Symbolic {
+    // Terms with the same part besides their coefficient are like terms
+    let mut like_terms: Vec<(Symbolic, BigRational)> = vec![];
+    for term in a.terms().into_iter().chain(b.terms()) {
+        let (coefficient, rest) = term.split_coefficient();
+        match like_terms.iter_mut().find(|(other, _)| *other == rest) {
+            Some((_, sum)) => *sum += coefficient,
+            None => like_terms.push((rest, coefficient)),
+        }
+    }
+    let mut terms = like_terms.into_iter()
+        .filter(|(_, coefficient)| !coefficient.is_zero())
+        .map(|(rest, coefficient)| mul(Symbolic::Constant(coefficient), rest))
+        .collect::<Vec<_>>();
+    terms.sort_by(compare_terms);
+    match terms.len() {
+        0 => integer(0),
+        1 => terms.remove(0),
+        _ => Symbolic::Sum(terms),
+    }
+}
+
+pub fn sub(a: Symbolic, b: Symbolic) -> Symbolic {
+    add(a, neg(b))
+}
+
+pub fn neg(a: Symbolic) -> Symbolic {
+    mul(integer(-1), a)
+}
+
+pub fn mul(a: Symbolic, b: Symbolic) -> Symbolic {
+    // Factors with the same base are combined by adding their exponents
+    let mut coefficient = BigRational::one();
+    let mut powers: Vec<(Symbolic, Symbolic)> = vec![];
+    for factor in a.factors().into_iter().chain(b.factors()) {
+        if let Symbolic::Constant(constant) = factor {
+            coefficient *= constant;
+            continue;
+        }
+        let (base, exponent) = factor.split_power();
+        match powers.iter_mut().find(|(other, _)| *other == base) {
+            Some((_, sum)) => *sum = add(sum.clone(), exponent),
+            None => powers.push((base, exponent)),
+        }
+    }
+    let mut factors = vec![];
+    // (xy)^{1/2} (xy)^{1/2} is a product again
+    let mut products = vec![];
+    for (base, exponent) in powers {
+        match pow(base, exponent) {
+            Symbolic::Constant(constant) => coefficient *= constant,
+            product @ Symbolic::Product(_) => products.push(product),
+            factor => factors.push(factor),
+        }
+    }
+    if coefficient.is_zero() {
+        return integer(0);
+    }
+    sort_factors(&mut factors);
+    let product = match (factors.len(), coefficient.is_one()) {
+        (0, _) => Symbolic::Constant(coefficient),
+        (1, true) => factors.remove(0),
+        // 2(x + 1) is 2x + 2
+        (1, false) if matches!(factors[0], Symbolic::Sum(_)) => {
+            let terms = factors.remove(0).terms();
+            terms.into_iter().fold(integer(0), |sum, term| add(sum, mul(Symbolic::Constant(coefficient.clone()), term)))
+        },
+        (_, true) => Symbolic::Product(factors),
+        (_, false) => Symbolic::Product([Symbolic::Constant(coefficient)].into_iter().chain(factors).collect()),
+    };
+    products.into_iter().fold(product, mul)
+}
+
+// Factors that are already as simple as they get, built directly since mul would multiply
+// 2(x + 1) back out
+pub fn product(factors: Vec<Symbolic>) -> Symbolic {
+    let (constants, mut factors): (Vec<_>, Vec<_>) = factors.into_iter().partition(|factor| matches!(factor, Symbolic::Constant(_)));
+    let coefficient = constants.into_iter().fold(BigRational::one(), |product, constant| match constant {
+        Symbolic::Constant(constant) => product * constant,
+        _ => product,
+    });
+    if coefficient.is_zero() || factors.is_empty() {
+        return Symbolic::Constant(coefficient);
+    }
+    sort_factors(&mut factors);
+    if !coefficient.is_one() {
+        factors.insert(0, Symbolic::Constant(coefficient));
+    }
+    if factors.len() == 1 { factors.remove(0) } else { Symbolic::Product(factors) }
+}
+
+pub fn div(a: Symbolic, b: Symbolic) -> Symbolic {
+    mul(a, pow(b, integer(-1)))
+}
+
+pub fn pow(base: Symbolic, exponent: Symbolic) -> Symbolic {
+    let whole = match &exponent {
+        Symbolic::Constant(exponent) if exponent.is_integer() => exponent.to_integer().to_i32(),
+        _ => None,
+    };
+    match (base, exponent) {
+        (_, Symbolic::Constant(exponent)) if exponent.is_zero() => integer(1),
+        (base, Symbolic::Constant(exponent)) if exponent.is_one() => base,
+        (Symbolic::Constant(base), _) if base.is_one() => integer(1),
+        (Symbolic::Constant(base), Symbolic::Constant(exponent)) if base.is_zero() && exponent.is_positive() => integer(0),
+        (Symbolic::Constant(base), _) if whole.is_some() && !base.is_zero() => Symbolic::Constant(rational_pow(&base, whole.unwrap())),
+        // (x^a)^n = x^{an} only holds for whole n, (x^2)^{1/2} is |x|
+        (Symbolic::Power(base, inner), exponent) if whole.is_some() => pow(*base, mul(*inner, exponent)),
+        (Symbolic::Product(factors), exponent) if whole.is_some() => {
+            factors.into_iter().fold(integer(1), |product, factor| mul(product, pow(factor, exponent.clone())))
+        },
+        (Symbolic::Variable(Identifier::TextIdent(name)), _) if name == "i" && whole.is_some() => match whole.unwrap().rem_euclid(4) {
+            0 => integer(1),
+            1 => variable("i"),
+            2 => integer(-1),
+            _ => neg(variable("i")),
+        },
+        (base, exponent) => Symbolic::Power(Box::new(base), Box::new(exponent)),
+    }
+}
+
+fn rational_pow(base: &BigRational, exponent: i32) -> BigRational {
+    let power = BigRational::new(num::pow(base.numer().clone(), exponent.unsigned_abs() as usize), num::pow(base.denom().clone(), exponent.unsigned_abs() as usize));
+    if exponent < 0 { power.recip() } else { power }
+}
+
+// The values that are known exactly, anything else is left as a call
+pub fn call(function: Builtin, args: Vec<Symbolic>) -> Symbolic {
+    let zero = |x: &Symbolic| *x == integer(0);
+    // A whole multiple of \pi
+    let pi_multiple = |x: &Symbolic| match x.clone().split_coefficient() {
+        (k, rest) if rest == pi() && k.is_integer() => k.to_integer().to_i64(),
+        _ => None,
+    };
+    match (&function, &args[..]) {
+        (Builtin::Expand, [x]) => x.clone().expand(),
+        (Builtin::Factor, [x]) => x.clone().factor(),
+        (Builtin::Exp, [x]) => pow(variable("e"), x.clone()),
+        (Builtin::Sin | Builtin::Tan | Builtin::Arcsin | Builtin::Arctan | Builtin::Sinh | Builtin::Tanh, [x]) if zero(x) => integer(0),
+        (Builtin::Cos | Builtin::Cosh, [x]) if zero(x) => integer(1),
+        (Builtin::Sin | Builtin::Tan, [x]) if pi_multiple(x).is_some() => integer(0),
+        (Builtin::Cos, [x]) if pi_multiple(x).is_some() => integer(if pi_multiple(x).unwrap() % 2 == 0 { 1 } else { -1 }),
+        (Builtin::Ln | Builtin::Log, [x, ..]) if *x == integer(1) => integer(0),
+        (Builtin::Ln, [x]) if *x == variable("e") => integer(1),
+        (Builtin::Log, [x, base]) if x == base => integer(1),
+        (Builtin::Ln, [Symbolic::Power(base, exponent)]) if **base == variable("e") => *exponent.clone(),
+        _ => Symbolic::Call(function, args),
+    }
+}
+
+impl Symbolic {
+    pub fn from_number(number: &Number) -> Option<Self> {
+        let real = |real: f64| Real::from_f64(real).map(|real| Symbolic::Constant(real.to_rational()));
+        match number {
+            Number::Integer(int)   => Some(Symbolic::Constant(BigRational::from_integer(int.clone()))),
+            Number::Rational(rat)  => Some(Symbolic::Constant(rat.clone())),
+            Number::Real(real)     => Some(Symbolic::Constant(real.to_rational())),
+            Number::PiMultiple(coefficient) => Some(mul(Symbolic::Constant(coefficient.clone()), pi())),
+            Number::Complex(cplx)  => Some(add(real(cplx.real)?, mul(real(cplx.imag)?, variable("i")))),
+            Number::Symbolic(symbolic) => Some(*symbolic.clone()),
+            Number::Polynomial(polynomial) => Some(polynomial.to_symbolic()),
+            Number::Infinity(_) | Number::Matrix(_) | Number::Tuple(_) | Number::Set(_) => None,
+        }
+    }
+
+    // Back to a number once nothing symbolic is left
+    pub fn into_number(self) -> Number {
+        match self {
+            Symbolic::Constant(rat) if rat.is_integer() => Number::Integer(rat.to_integer()),
+            Symbolic::Constant(rat) => Number::Rational(rat),
+            symbolic => Number::Symbolic(Box::new(symbolic)),
+        }
+    }
+
+    fn terms(self) -> Vec<Symbolic> {
+        match self {
+            Symbolic::Sum(terms) => terms,
+            Symbolic::Constant(constant) if constant.is_zero() => vec![],
+            term => vec![term],
+        }
+    }
+
+    fn factors(self) -> Vec<Symbolic> {
+        match self {
+            Symbolic::Product(factors) => factors,
+            Symbolic::Constant(constant) if constant.is_one() => vec![],
+            factor => vec![factor],
+        }
+    }
+
+    // 3x^2 is 3 and x^2, a constant is itself and 1
+    fn split_coefficient(self) -> (BigRational, Symbolic) {
+        match self {
+            Symbolic::Constant(constant) => (constant, integer(1)),
+            Symbolic::Product(mut factors) => match factors.first() {
+                Some(Symbolic::Constant(constant)) => {
+                    let constant = constant.clone();
+                    factors.remove(0);
+                    let rest = if factors.len() == 1 { factors.remove(0) } else { Symbolic::Product(factors) };
+                    (constant, rest)
+                },
+                _ => (BigRational::one(), Symbolic::Product(factors)),
+            },
+            term => (BigRational::one(), term),
+        }
+    }
+
+    fn split_power(self) -> (Symbolic, Symbolic) {
+        match self {
+            Symbolic::Power(base, exponent) => (*base, *exponent),
+            factor => (factor, integer(1)),
+        }
+    }
+
+    // Of its variables, what decides the order of terms. Anything that isn't a polynomial counts as a constant
+    fn degree(&self) -> BigRational {
+        match self {
+            Symbolic::Variable(_) => BigRational::one(),
+            Symbolic::Sum(terms) => terms.iter().map(Symbolic::degree).max().unwrap_or_else(BigRational::zero),
+            Symbolic::Product(factors) => factors.iter().map(Symbolic::degree).sum(),
+            Symbolic::Power(base, exponent) => match &**exponent {
+                Symbolic::Constant(exponent) => base.degree() * exponent,
+                _ => BigRational::zero(),
+            },
+            _ => BigRational::zero(),
+        }
+    }
+
+    // Multiplies out every product of sums and every whole power of a sum
+    pub fn expand(self) -> Symbolic {
+        match self {
+            Symbolic::Sum(terms) => terms.into_iter().map(Symbolic::expand).fold(integer(0), add),
+            Symbolic::Product(factors) => factors.into_iter().map(Symbolic::expand).fold(integer(1), distribute),
+            Symbolic::Power(base, exponent) => {
+                let base = base.expand();
+                match *exponent {
+                    Symbolic::Constant(n) if matches!(base, Symbolic::Sum(_)) && n.is_integer() && n.is_positive() => {
+                        num::range(BigInt::zero(), n.to_integer()).fold(integer(1), |product, _| distribute(product, base.clone()))
+                    },
+                    exponent => pow(base, exponent.expand()),
+                }
+            },
+            Symbolic::Call(function, args) => call(function, args.into_iter().map(Symbolic::expand).collect()),
+            Symbolic::Operation(unop, value) => Symbolic::Operation(unop, Box::new(value.expand())),
+            symbolic => symbolic,
+        }
+    }
+
+    // Takes out what every term has in common, then factors a polynomial in one variable over \Q,
+    // 2x^2 - 2 is 2(x + 1)(x - 1)
+    pub fn factor(self) -> Symbolic {
+        let expanded = self.expand();
+        let terms = expanded.clone().terms();
+        if terms.len() < 2 {
+            return expanded;
+        }
+        let split = terms.into_iter().map(|term| {
+            let (coefficient, rest) = term.split_coefficient();
+            (coefficient, rest.factors().into_iter().map(Symbolic::split_power).collect::<Vec<_>>())
+        }).collect::<Vec<_>>();
+
+        // The gcd of the coefficients, negative when the first term is
+        let numerators = split.iter().fold(BigInt::zero(), |gcd, (coefficient, _)| gcd.gcd(coefficient.numer()));
+        let denominators = split.iter().fold(BigInt::one(), |lcm, (coefficient, _)| lcm.lcm(coefficient.denom()));
+        let mut content = BigRational::new(numerators, denominators);
+        if split[0].0.is_negative() {
+            content = -content;
+        }
+        // The lowest whole power of each base that is in every term
+        let mut common = vec![];
+        for (base, _) in &split[0].1 {
+            let lowest = split.iter().map(|(_, powers)| powers.iter().find_map(|(other, exponent)| match exponent {
+                Symbolic::Constant(exponent) if other == base && exponent.is_integer() && exponent.is_positive() => Some(exponent.clone()),
+                _ => None,
+            })).collect::<Option<Vec<_>>>().and_then(|exponents| exponents.into_iter().min());
+            if let Some(lowest) = lowest {
+                common.push(pow(base.clone(), Symbolic::Constant(lowest)));
+            }
+        }
+        let divisor = common.iter().cloned().fold(Symbolic::Constant(content.clone()), mul);
+        let remaining = div(expanded, divisor).expand();
+
+        let mut factors = common;
+        match remaining.polynomial().and_then(|polynomial| polynomial.factor()) {
+            Some((constant, irreducible)) => {
+                content *= constant;
+                for (factor, multiplicity) in irreducible {
+                    factors.push(pow(factor.to_symbolic(), integer(multiplicity as i64)));
+                }
+            },
+            None => factors.push(remaining),
+        }
+        factors.push(Symbolic::Constant(content));
+        product(factors)
+    }
+
+    // d/dx, None for what has no derivative here like \lfloor x \rfloor
+    pub fn differentiate(&self, x: &Identifier) -> Option<Symbolic> {
+        Some(match self {
+            Symbolic::Constant(_) => integer(0),
+            Symbolic::Variable(variable) => integer(if variable == x { 1 } else { 0 }),
+            Symbolic::Sum(terms) => {
+                let mut sum = integer(0);
+                for term in terms {
+                    sum = add(sum, term.differentiate(x)?);
+                }
+                sum
+            },
+            // One term for each factor, with that factor differentiated
+            Symbolic::Product(factors) => {
+                let mut sum = integer(0);
+                for (i, factor) in factors.iter().enumerate() {
+                    let others = factors.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, other)| other.clone());
+                    sum = add(sum, others.fold(factor.differentiate(x)?, mul));
+                }
+                sum
+            },
+            Symbolic::Power(base, exponent) => {
+                let (d_base, d_exponent) = (base.differentiate(x)?, exponent.differentiate(x)?);
+                if d_exponent == integer(0) {
+                    mul(mul(*exponent.clone(), pow(*base.clone(), sub(*exponent.clone(), integer(1)))), d_base)
+                } else {
+                    // (b^e)' = b^e (e' \ln b + e b' / b)
+                    let log = call(Builtin::Ln, vec![*base.clone()]);
+                    mul(self.clone(), add(mul(d_exponent, log), div(mul(*exponent.clone(), d_base), *base.clone())))
+                }
+            },
+            Symbolic::Call(Builtin::Log, args) if args.len() == 2 => {
+                div(call(Builtin::Ln, vec![args[0].clone()]), call(Builtin::Ln, vec![args[1].clone()])).differentiate(x)?
+            },
+            // The chain rule, f(u)' = f'(u) u'
+            Symbolic::Call(function, args) if args.len() == 1 => {
+                let u = args[0].clone();
+                let of = |function: Builtin| call(function, vec![u.clone()]);
+                let outer = match function {
+                    Builtin::Sin    => of(Builtin::Cos),
+                    Builtin::Cos    => neg(of(Builtin::Sin)),
+                    Builtin::Tan    => pow(of(Builtin::Sec), integer(2)),
+                    Builtin::Sec    => mul(of(Builtin::Sec), of(Builtin::Tan)),
+                    Builtin::Csc    => neg(mul(of(Builtin::Csc), of(Builtin::Cot))),
+                    Builtin::Cot    => neg(pow(of(Builtin::Csc), integer(2))),
+                    Builtin::Arcsin => pow(sub(integer(1), pow(u.clone(), integer(2))), Symbolic::Constant(BigRational::new((-1).into(), 2.into()))),
+                    Builtin::Arccos => neg(pow(sub(integer(1), pow(u.clone(), integer(2))), Symbolic::Constant(BigRational::new((-1).into(), 2.into())))),
+                    Builtin::Arctan => pow(add(integer(1), pow(u.clone(), integer(2))), integer(-1)),
+                    Builtin::Sinh   => of(Builtin::Cosh),
+                    Builtin::Cosh   => of(Builtin::Sinh),
+                    Builtin::Tanh   => pow(of(Builtin::Cosh), integer(-2)),
+                    Builtin::Coth   => neg(pow(of(Builtin::Sinh), integer(-2))),
+                    Builtin::Ln     => pow(u.clone(), integer(-1)),
+                    Builtin::Log    => pow(mul(u.clone(), call(Builtin::Ln, vec![integer(10)])), integer(-1)),
+                    Builtin::Exp    => pow(variable("e"), u.clone()),
+                    _ => return None,
+                };
+                mul(outer, u.differentiate(x)?)
+            },
+            // |u|' = u u' / |u|
+            Symbolic::Operation(UnOps::AbsoluteValue, u) => mul(div(*u.clone(), self.clone()), u.differentiate(x)?),
+            _ => return None,
+        })
+    }
+
+    // An antiderivative without the constant, for sums of elementary functions of ax + b
+    pub fn integrate(&self, x: &Identifier) -> Option<Symbolic> {
+        if !self.contains(x) {
+            return Some(mul(self.clone(), Symbolic::Variable(x.clone())));
+        }
+        // a in ax + b, substituting u = ax + b divides by it
+        let slope = |u: &Symbolic| u.differentiate(x).filter(|a| !a.contains(x) && *a != integer(0));
+        let squared = pow(Symbolic::Variable(x.clone()), integer(2));
+        let half = Symbolic::Constant(BigRational::new((-1).into(), 2.into()));
+        Some(match self {
+            Symbolic::Variable(_) => div(squared, integer(2)),
+            Symbolic::Sum(terms) => {
+                let mut sum = integer(0);
+                for term in terms {
+                    sum = add(sum, term.integrate(x)?);
+                }
+                sum
+            },
+            Symbolic::Product(factors) => {
+                let (constant, varying): (Vec<_>, Vec<_>) = factors.iter().cloned().partition(|factor| !factor.contains(x));
+                match &varying[..] {
+                    [factor] => mul(constant.into_iter().fold(integer(1), mul), factor.integrate(x)?),
+                    // x(x + 1) is integrated term by term once expanded
+                    _ => {
+                        let expanded = self.clone().expand();
+                        if expanded == *self {
+                            return None;
+                        }
+                        expanded.integrate(x)?
+                    },
+                }
+            },
+            Symbolic::Power(base, exponent) if **base == add(squared.clone(), integer(1)) && **exponent == integer(-1) => {
+                call(Builtin::Arctan, vec![Symbolic::Variable(x.clone())])
+            },
+            Symbolic::Power(base, exponent) if **base == sub(integer(1), squared.clone()) && **exponent == half => {
+                call(Builtin::Arcsin, vec![Symbolic::Variable(x.clone())])
+            },
+            // \sec^2 u and \csc^2 u
+            Symbolic::Power(base, exponent) if **exponent == integer(2) && matches!(**base, Symbolic::Call(Builtin::Sec | Builtin::Csc, _)) => {
+                let Symbolic::Call(function, args) = &**base else { unreachable!() };
+                let u = args.first()?;
+                match function {
+                    Builtin::Sec => div(call(Builtin::Tan, vec![u.clone()]), slope(u)?),
+                    _            => neg(div(call(Builtin::Cot, vec![u.clone()]), slope(u)?)),
+                }
+            },
+            Symbolic::Power(base, exponent) if !exponent.contains(x) => {
+                let a = slope(base)?;
+                if **exponent == integer(-1) {
+                    div(call(Builtin::Ln, vec![Symbolic::Operation(UnOps::AbsoluteValue, base.clone())]), a)
+                } else {
+                    let n = add(*exponent.clone(), integer(1));
+                    div(pow(*base.clone(), n.clone()), mul(n, a))
+                }
+            },
+            // e^u and b^u
+            Symbolic::Power(base, exponent) if !base.contains(x) => {
+                div(self.clone(), mul(slope(exponent)?, call(Builtin::Ln, vec![*base.clone()])))
+            },
+            Symbolic::Call(function, args) if args.len() == 1 => {
+                let u = args[0].clone();
+                let of = |function: Builtin| call(function, vec![u.clone()]);
+                let absolute = |value: Symbolic| Symbolic::Operation(UnOps::AbsoluteValue, Box::new(value));
+                let antiderivative = match function {
+                    Builtin::Sin  => neg(of(Builtin::Cos)),
+                    Builtin::Cos  => of(Builtin::Sin),
+                    Builtin::Tan  => neg(call(Builtin::Ln, vec![absolute(of(Builtin::Cos))])),
+                    Builtin::Cot  => call(Builtin::Ln, vec![absolute(of(Builtin::Sin))]),
+                    Builtin::Sinh => of(Builtin::Cosh),
+                    Builtin::Cosh => of(Builtin::Sinh),
+                    Builtin::Tanh => call(Builtin::Ln, vec![of(Builtin::Cosh)]),
+                    Builtin::Ln   => sub(mul(u.clone(), of(Builtin::Ln)), u.clone()),
+                    Builtin::Log  => div(sub(mul(u.clone(), of(Builtin::Ln)), u.clone()), call(Builtin::Ln, vec![integer(10)])),
+                    _ => return None,
+                };
+                div(antiderivative, slope(&u)?)
+            },
+            _ => return None,
+        })
+    }
+
+    // p(x) / q(x) with the coefficients lowest degree first, when that's all it is
+    pub fn rational_function(&self, x: &Identifier) -> Option<(Vec<BigRational>, Vec<BigRational>)> {
+        let one = || vec![BigRational::one()];
+        Some(match self {
+            Symbolic::Constant(constant) => (vec![constant.clone()], one()),
+            Symbolic::Variable(variable) if variable == x => (vec![BigRational::zero(), BigRational::one()], one()),
+            Symbolic::Sum(terms) => {
+                let mut sum = (vec![], one());
+                for term in terms {
+                    let (numerator, denominator) = term.rational_function(x)?;
+                    sum = (polynomial::add(&polynomial::mul(&sum.0, &denominator), &polynomial::mul(&numerator, &sum.1)), polynomial::mul(&sum.1, &denominator));
+                }
+                sum
+            },
+            Symbolic::Product(factors) => {
+                let mut product = (one(), one());
+                for factor in factors {
+                    let (numerator, denominator) = factor.rational_function(x)?;
+                    product = (polynomial::mul(&product.0, &numerator), polynomial::mul(&product.1, &denominator));
+                }
+                product
+            },
+            Symbolic::Power(base, exponent) => {
+                let Symbolic::Constant(exponent) = &**exponent else { return None };
+                let n = exponent.is_integer().then(|| exponent.to_integer().to_i32()).flatten()?;
+                let (mut numerator, mut denominator) = base.rational_function(x)?;
+                if n < 0 {
+                    std::mem::swap(&mut numerator, &mut denominator);
+                }
+                let power = |polynomial: &Vec<BigRational>| (0..n.unsigned_abs()).fold(one(), |power, _| polynomial::mul(&power, polynomial));
+                (power(&numerator), power(&denominator))
+            },
+            _ => return None,
+        })
+    }
+
+    // Whether x appears anywhere in it
+    fn contains(&self, x: &Identifier) -> bool {
+        match self {
+            Symbolic::Constant(_) => false,
+            Symbolic::Variable(variable) => variable == x,
+            Symbolic::Sum(parts) | Symbolic::Product(parts) | Symbolic::Call(_, parts) => parts.iter().any(|part| part.contains(x)),
+            Symbolic::Power(base, exponent) => base.contains(x) || exponent.contains(x),
+            Symbolic::Operation(_, value) => value.contains(x),
+        }
+    }
+
+    // Back to a value the interpreter can evaluate, once its variables have values
+    pub fn to_value(&self) -> Value {
+        let operation = |value_1, binop, value_2| Value::Expression(Box::new(Expression::BinaryOperation(BinaryOperation { value_1, binop, value_2 })));
+        match self {
+            Symbolic::Constant(_) => Value::Number(self.clone().into_number()),
+            Symbolic::Variable(variable) => Value::Identifier(variable.clone()),
+            Symbolic::Sum(terms) => terms.iter().map(Symbolic::to_value).reduce(|sum, term| operation(sum, BinOps::Addition, term)).unwrap(),
+            Symbolic::Product(factors) => factors.iter().map(Symbolic::to_value).reduce(|product, factor| operation(product, BinOps::Multiply, factor)).unwrap(),
+            Symbolic::Power(base, exponent) => operation(base.to_value(), BinOps::Power, exponent.to_value()),
+            Symbolic::Call(function, args) => {
+                Value::Expression(Box::new(Expression::BuiltinCall(BuiltinCall { function: function.clone(), args: args.iter().map(Symbolic::to_value).collect() })))
+            },
+            Symbolic::Operation(unop, value) => Value::Expression(Box::new(Expression::UnaryOperation(UnaryOperation { unop: unop.clone(), value: value.to_value() }))),
+        }
+    }
+
+    // A polynomial in one variable with integer coefficients
+    fn polynomial(&self) -> Option<Polynomial> {
+        let mut variable = None;
+        let mut coefficients: Vec<BigInt> = vec![];
+        for term in self.clone().terms() {
+            let (coefficient, rest) = term.split_coefficient();
+            if !coefficient.is_integer() {
+                return None;
+            }
+            let (base, degree) = match rest.split_power() {
+                (base, _) if base == integer(1) => (None, 0),
+                (Symbolic::Variable(base), Symbolic::Constant(degree)) if degree.is_integer() && degree.is_positive() => (Some(base), degree.to_integer().to_usize()?),
+                _ => return None,
+            };
+            if let Some(base) = base {
+                if variable.get_or_insert(base.clone()) != &base {
+                    return None;
+                }
+            }
+            if coefficients.len() <= degree {
+                coefficients.resize(degree + 1, BigInt::zero());
+            }
+            coefficients[degree] += coefficient.to_integer();
+        }
+        Polynomial::new(variable?, Ring::Integers, coefficients.into_iter().map(BigRational::from_integer).collect())
+    }
+}
+
+fn distribute(a: Symbolic, b: Symbolic) -> Symbolic {
+    let b = b.terms();
+    a.terms().into_iter()
+        .flat_map(|x| b.iter().map(move |y| mul(x.clone(), y.clone())))
+        .fold(integer(0), add)
+}
+
+// Highest degree first, constants last, otherwise alphabetically ignoring coefficients
+fn compare_terms(a: &Symbolic, b: &Symbolic) -> Ordering {
+    let constant = |term: &Symbolic| matches!(term, Symbolic::Constant(_));
+    b.degree().cmp(&a.degree())
+        .then(constant(a).cmp(&constant(b)))
+        .then_with(|| a.clone().split_coefficient().1.to_string().cmp(&b.clone().split_coefficient().1.to_string()))
+}
+
+// Variables, then sums and powers like e^x, then calls, x(x + 1) \sin x
+fn sort_factors(factors: &mut [Symbolic]) {
+    let rank = |factor: &Symbolic| match factor {
+        Symbolic::Variable(_) => 0,
+        Symbolic::Power(base, exponent) if matches!(**base, Symbolic::Variable(_)) && matches!(**exponent, Symbolic::Constant(_)) => 0,
+        Symbolic::Sum(_) | Symbolic::Power(..) => 1,
+        _ => 2,
+    };
+    factors.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.to_string().cmp(&b.to_string())));
+}
+
+// Factors side by side, with a space between letters and a \cdot before a number.
+// A sum on its own in a \frac doesn't need parentheses
+fn juxtapose(coefficient: &BigInt, factors: &[Symbolic], fraction: bool) -> String {
+    let mut pieces = vec![];
+    if !coefficient.is_one() || factors.is_empty() {
+        pieces.push(coefficient.to_string());
+    }
+    let alone = fraction && pieces.is_empty() && factors.len() == 1;
+    pieces.extend(factors.iter().map(|factor| match factor {
+        Symbolic::Sum(_) if !alone => format!("({factor})"),
+        factor => factor.to_string(),
+    }));
+    let mut written = String::new();
+    for piece in pieces {
+        let (last, first) = (written.chars().last(), piece.chars().next());
+        match (last, first) {
+            (Some(_), Some(first)) if first.is_ascii_digit() => written.push_str(" \\cdot "),
+            (Some(last), Some(first)) if last.is_ascii_alphabetic() && first.is_ascii_alphabetic() => written.push(' '),
+            // x^2 \cos x, not x^2\cos x
+            (Some(_), Some('\\')) => written.push(' '),
+            _ => (),
+        }
+        written.push_str(&piece);
+    }
+    written
+}
+
+// x^2, x^{10}, \sqrt{x}
+fn write_power(f: &mut std::fmt::Formatter<'_>, base: &Symbolic, exponent: &Symbolic) -> std::fmt::Result {
+    if *exponent == Symbolic::Constant(BigRational::new(1.into(), 2.into())) {
+        return write!(f, "\\sqrt{{{base}}}");
+    }
+    match base {
+        Symbolic::Variable(_) => write!(f, "{base}")?,
+        Symbolic::Constant(constant) if constant.is_integer() && !constant.is_negative() => write!(f, "{base}")?,
+        base => write!(f, "({base})")?,
+    }
+    let exponent = exponent.to_string();
+    if exponent.chars().count() == 1 {
+        write!(f, "^{exponent}")
+    } else {
+        write!(f, "^{{{exponent}}}")
+    }
+}
+
+// A coefficient and factors, with whatever has a negative exponent under a \frac
+fn write_product(f: &mut std::fmt::Formatter<'_>, product: &Symbolic) -> std::fmt::Result {
+    let (coefficient, rest) = product.clone().split_coefficient();
+    let (mut numerator, mut denominator) = (vec![], vec![]);
+    for factor in rest.factors() {
+        match factor.clone().split_power() {
+            (base, Symbolic::Constant(exponent)) if exponent.is_negative() => denominator.push(pow(base, Symbolic::Constant(-exponent))),
+            _ => numerator.push(factor),
+        }
+    }
+    if coefficient.is_negative() {
+        write!(f, "-")?;
+    }
+    if denominator.is_empty() && coefficient.denom().is_one() {
+        write!(f, "{}", juxtapose(&coefficient.numer().abs(), &numerator, false))
+    } else {
+        write!(f, "\\frac{{{}}}{{{}}}", juxtapose(&coefficient.numer().abs(), &numerator, true), juxtapose(coefficient.denom(), &denominator, true))
+    }
+}
+
+// Already LaTeX, which is what both renderers print
+impl Display for Symbolic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Symbolic::Variable(ident) => write!(f, "{ident}"),
+            Symbolic::Sum(terms) => {
+                for (i, term) in terms.iter().enumerate() {
+                    let (coefficient, rest) = term.clone().split_coefficient();
+                    let sign = match (i, coefficient.is_negative()) {
+                        (0, _)     => "",
+                        (_, true)  => " - ",
+                        (_, false) => " + ",
+                    };
+                    let term = if i > 0 { mul(Symbolic::Constant(coefficient.abs()), rest) } else { term.clone() };
+                    write!(f, "{sign}{term}")?;
+                }
+                Ok(())
+            },
+            Symbolic::Power(base, exponent) if !matches!(**exponent, Symbolic::Constant(ref exponent) if exponent.is_negative()) => write_power(f, base, exponent),
+            Symbolic::Constant(_) | Symbolic::Product(_) | Symbolic::Power(..) => write_product(f, self),
+            Symbolic::Call(Builtin::Log, args) if args.len() == 2 => {
+                let base = args[1].to_string();
+                if base.chars().count() == 1 { write!(f, "\\log_{base}")? } else { write!(f, "\\log_{{{base}}}")? }
+                write_argument(f, &args[0])
+            },
+            Symbolic::Call(function, args) if args.len() == 1 => {
+                write!(f, "{function}")?;
+                write_argument(f, &args[0])
+            },
+            Symbolic::Call(function, args) => {
+                write!(f, "{function}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    write!(f, "{}{arg}", if i > 0 { ", " } else { "" })?;
+                }
+                write!(f, ")")
+            },
+            Symbolic::Operation(UnOps::AbsoluteValue, value) => write!(f, "|{value}|"),
+            Symbolic::Operation(UnOps::Floor, value)   => write!(f, "\\lfloor {value} \\rfloor"),
+            Symbolic::Operation(UnOps::Ceiling, value) => write!(f, "\\lceil {value} \\rceil"),
+            // Only factorials are left
+            Symbolic::Operation(_, value) => match **value {
+                Symbolic::Variable(_) => write!(f, "{value}!"),
+                _ => write!(f, "({value})!"),
+            },
+        }
+    }
+}
+
+// \sin x and \sin 2, but \sin(x + 1)
+fn write_argument(f: &mut std::fmt::Formatter<'_>, argument: &Symbolic) -> std::fmt::Result {
+    match argument {
+        Symbolic::Variable(_) => write!(f, " {argument}"),
+        Symbolic::Constant(constant) if constant.is_integer() && !constant.is_negative() => write!(f, " {argument}"),
+        // \ln |x|, the bars are brackets already
+        Symbolic::Operation(UnOps::AbsoluteValue, _) => write!(f, " {argument}"),
+        argument => write!(f, "({argument})"),
+    }
+}
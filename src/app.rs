@@ -1,4 +1,4 @@
 use crate::config::Config;
 
-pub fn run(config: Config) {
+pub fn run(_config: Config) {
 }